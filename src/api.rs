@@ -0,0 +1,302 @@
+use crate::job::{JobSpec, SpecError};
+#[cfg(feature = "metrics")]
+use crate::metrics::Metrics;
+use crate::queue::{PushError, QueueManager};
+use serde_json::{Value, json};
+use std::io;
+use std::net::{SocketAddr, ToSocketAddrs};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use tiny_http::{Header, Method, Request, Response, Server};
+use uuid::Uuid;
+
+/// A small HTTP front end for submitting and managing jobs from other
+/// services, sharing the queue the engine dispatches from:
+///
+/// - `POST /jobs` takes a `JobSpec` as JSON and answers `201 {"id": ...}`,
+///   `400 {"error": ...}` if the spec is invalid (e.g. in the past, or naming
+///   a function the queue doesn't know), `409` if its idempotency key is
+///   already queued, or `503` if the queue is at its `try_push` capacity.
+/// - `POST /jobs/validate` checks a `JobSpec` the same way without queueing
+///   it, including that the queue knows its function: `200 {"valid": true}`,
+///   or `400 {"error": ..., "details": ...}` with the `SpecError` as JSON.
+///
+/// A spec with a `priority` outside 0-255 or an `execution_time` in the past
+/// is refused with `400`, or adjusted to the nearest valid value with
+/// `OutOfRange::Clamp` (see `ApiOptions`).
+/// - `GET /jobs` returns the queued jobs in dispatch order.
+/// - `DELETE /jobs/{id}` removes a queued job: `204`, or `404` if it isn't queued.
+/// - `GET /metrics` serves Prometheus metrics, when started with
+///   `start_with_metrics` (requires the `metrics` feature).
+pub struct ApiServer {
+    server: Arc<Server>,
+    addr: SocketAddr,
+    handle: Option<JoinHandle<()>>,
+}
+
+/// What the API does with a submitted value outside its valid range.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OutOfRange {
+    /// Refuse the spec with `400`.
+    #[default]
+    Reject,
+    /// Use the nearest valid value instead: priorities are clamped to 0-255
+    /// and past execution times move up to now.
+    Clamp,
+}
+
+/// Settings for `ApiServer::start_with_options`.
+#[derive(Clone, Default)]
+pub struct ApiOptions {
+    out_of_range: OutOfRange,
+    #[cfg(feature = "metrics")]
+    metrics: Option<Arc<Metrics>>,
+}
+
+impl ApiOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// How submitted specs with out-of-range values are handled;
+    /// `OutOfRange::Reject` by default.
+    pub fn with_out_of_range(mut self, out_of_range: OutOfRange) -> Self {
+        self.out_of_range = out_of_range;
+        self
+    }
+
+    /// Serves `metrics` at `GET /metrics`.
+    #[cfg(feature = "metrics")]
+    pub fn with_metrics(mut self, metrics: Arc<Metrics>) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+}
+
+impl ApiServer {
+    /// Binds `addr` and serves requests on a background thread until `stop`.
+    /// Bind to port 0 to pick a free port, then read it from `local_addr`.
+    pub fn start(
+        addr: impl ToSocketAddrs,
+        queue: Arc<Mutex<QueueManager>>,
+    ) -> io::Result<ApiServer> {
+        Self::start_with_options(addr, queue, ApiOptions::default())
+    }
+
+    /// Like `start`, also serving `metrics` at `GET /metrics`.
+    #[cfg(feature = "metrics")]
+    pub fn start_with_metrics(
+        addr: impl ToSocketAddrs,
+        queue: Arc<Mutex<QueueManager>>,
+        metrics: Arc<Metrics>,
+    ) -> io::Result<ApiServer> {
+        Self::start_with_options(addr, queue, ApiOptions::new().with_metrics(metrics))
+    }
+
+    /// Like `start`, configured by `options`.
+    pub fn start_with_options(
+        addr: impl ToSocketAddrs,
+        queue: Arc<Mutex<QueueManager>>,
+        options: ApiOptions,
+    ) -> io::Result<ApiServer> {
+        Self::serve(
+            addr,
+            State {
+                queue,
+                out_of_range: options.out_of_range,
+                #[cfg(feature = "metrics")]
+                metrics: options.metrics,
+            },
+        )
+    }
+
+    fn serve(addr: impl ToSocketAddrs, state: State) -> io::Result<ApiServer> {
+        let server = Server::http(addr).map_err(io::Error::other)?;
+        let addr = server
+            .server_addr()
+            .to_ip()
+            .ok_or_else(|| io::Error::other("API server is not bound to an IP address"))?;
+        let server = Arc::new(server);
+        let incoming = Arc::clone(&server);
+        let handle = thread::spawn(move || {
+            println!("[Api] Listening on http://{}", addr);
+            for request in incoming.incoming_requests() {
+                respond(request, &state);
+            }
+        });
+        Ok(ApiServer {
+            server,
+            addr,
+            handle: Some(handle),
+        })
+    }
+
+    pub fn local_addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// Stops accepting requests and waits for the serving thread to exit.
+    pub fn stop(mut self) {
+        self.shutdown();
+    }
+
+    fn shutdown(&mut self) {
+        self.server.unblock();
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+impl Drop for ApiServer {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+/// What the routes read from, shared with the serving thread.
+struct State {
+    queue: Arc<Mutex<QueueManager>>,
+    out_of_range: OutOfRange,
+    #[cfg(feature = "metrics")]
+    metrics: Option<Arc<Metrics>>,
+}
+
+enum Reply {
+    Json(Value),
+    /// Prometheus text exposition format
+    #[cfg_attr(not(feature = "metrics"), allow(dead_code))]
+    Text(String),
+    Empty,
+}
+
+fn respond(mut request: Request, state: &State) {
+    let mut body = String::new();
+    let (status, reply) = match request.as_reader().read_to_string(&mut body) {
+        Ok(_) => handle(state, request.method(), request.url(), &body),
+        Err(e) => (400, Reply::Json(json!({ "error": e.to_string() }))),
+    };
+    let (body, content_type) = match reply {
+        Reply::Json(value) => (value.to_string(), Some("application/json")),
+        Reply::Text(text) => (text, Some("text/plain; version=0.0.4")),
+        Reply::Empty => (String::new(), None),
+    };
+    let mut response = Response::from_string(body).with_status_code(status);
+    if let Some(content_type) = content_type {
+        response = response.with_header(
+            Header::from_bytes("Content-Type", content_type).expect("static header is valid"),
+        );
+    }
+    if let Err(e) = request.respond(response) {
+        eprintln!("[Api] Failed to send response: {}", e);
+    }
+}
+
+/// Routes one request, returning the status code and body.
+fn handle(state: &State, method: &Method, url: &str, body: &str) -> (u16, Reply) {
+    let error = |status, message: String| (status, Reply::Json(json!({ "error": message })));
+    let path = url.split('?').next().unwrap_or_default();
+    let queue = &state.queue;
+
+    match (method, path) {
+        (Method::Get, "/jobs") => {
+            let jobs = queue.lock().unwrap().snapshot();
+            (200, Reply::Json(json!(jobs)))
+        }
+        #[cfg(feature = "metrics")]
+        (Method::Get, "/metrics") if state.metrics.is_some() => {
+            let depth = queue.lock().unwrap().len();
+            let metrics = state.metrics.as_ref().expect("checked by the guard");
+            (200, Reply::Text(metrics.render(depth)))
+        }
+        (Method::Post, "/jobs") => {
+            let job = parse_spec(body, state.out_of_range).and_then(JobSpec::into_job);
+            match job {
+                Ok(job) => {
+                    let id = job.id;
+                    match queue.lock().unwrap().try_push(job) {
+                        Ok(()) => (201, Reply::Json(json!({ "id": id }))),
+                        Err(e) => {
+                            let status = match e {
+                                PushError::Full { .. } => 503,
+                                PushError::Duplicate { .. } => 409,
+                                PushError::UnknownFunction(_) | PushError::DependencyCycle(_) => {
+                                    400
+                                }
+                            };
+                            error(status, e.to_string())
+                        }
+                    }
+                }
+                Err(e) => error(400, e),
+            }
+        }
+        (Method::Post, "/jobs/validate") => match parse_spec(body, state.out_of_range) {
+            Ok(spec) => {
+                let queue = queue.lock().unwrap();
+                match spec.validate_with(|function| queue.knows_function(function)) {
+                    Ok(()) => (200, Reply::Json(json!({ "valid": true }))),
+                    Err(e) => spec_error(e),
+                }
+            }
+            Err(e) => error(400, e),
+        },
+        (Method::Delete, path) if path.starts_with("/jobs/") => {
+            match Uuid::parse_str(&path["/jobs/".len()..]) {
+                Ok(id) => match queue.lock().unwrap().remove(id) {
+                    Some(_) => (204, Reply::Empty),
+                    None => error(404, format!("job {} is not queued", id)),
+                },
+                Err(e) => error(400, format!("invalid job id: {}", e)),
+            }
+        }
+        (_, "/jobs" | "/jobs/validate") => {
+            error(405, format!("{} is not supported on {}", method, path))
+        }
+        _ => error(404, format!("no route for {}", path)),
+    }
+}
+
+/// A `400` reply for a spec that failed validation, with the structured
+/// reason alongside the message.
+fn spec_error(e: SpecError) -> (u16, Reply) {
+    (
+        400,
+        Reply::Json(json!({ "error": e.to_string(), "details": e })),
+    )
+}
+
+/// Parses a `JobSpec` from a request body, refusing or clamping a `priority`
+/// outside 0-255 and an `execution_time` in the past as `out_of_range` says.
+/// Under `OutOfRange::Reject` a past time is left for validation to refuse.
+fn parse_spec(body: &str, out_of_range: OutOfRange) -> Result<JobSpec, String> {
+    let mut value: Value = serde_json::from_str(body).map_err(|e| e.to_string())?;
+    let priority = value.get("priority").and_then(|p| {
+        p.as_i64()
+            .map(i128::from)
+            .or_else(|| p.as_u64().map(i128::from))
+    });
+    if let Some(priority) = priority
+        && u8::try_from(priority).is_err()
+    {
+        match out_of_range {
+            OutOfRange::Reject => {
+                return Err(format!(
+                    "priority {} is out of range ({}-{})",
+                    priority,
+                    u8::MIN,
+                    u8::MAX
+                ));
+            }
+            OutOfRange::Clamp => {
+                let nearest = if priority < 0 { u8::MIN } else { u8::MAX };
+                value["priority"] = nearest.into();
+            }
+        }
+    }
+    let mut spec: JobSpec = serde_json::from_value(value).map_err(|e| e.to_string())?;
+    if out_of_range == OutOfRange::Clamp {
+        spec.execution_time = spec.execution_time.max(chrono::Utc::now().timestamp());
+    }
+    Ok(spec)
+}
@@ -0,0 +1,64 @@
+use chrono::Utc;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::time::Duration;
+
+/// Where the engine and job validation read the current time from, so tests
+/// can control it instead of sleeping.
+pub trait Clock: Send + Sync {
+    /// Unix seconds.
+    fn now_secs(&self) -> i64;
+
+    /// Unix milliseconds. Defaults to whole seconds.
+    fn now_millis(&self) -> i64 {
+        self.now_secs() * 1000
+    }
+}
+
+/// The real wall clock. Used unless a different clock is supplied.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now_secs(&self) -> i64 {
+        Utc::now().timestamp()
+    }
+
+    fn now_millis(&self) -> i64 {
+        Utc::now().timestamp_millis()
+    }
+}
+
+/// A clock that only moves when told to. Clones share the same time, so a
+/// test can keep one and hand another to the engine.
+#[derive(Debug, Clone)]
+pub struct MockClock {
+    millis: Arc<AtomicI64>,
+}
+
+impl MockClock {
+    pub fn new(now_secs: i64) -> Self {
+        Self {
+            millis: Arc::new(AtomicI64::new(now_secs * 1000)),
+        }
+    }
+
+    pub fn advance(&self, by: Duration) {
+        self.millis
+            .fetch_add(by.as_millis() as i64, Ordering::SeqCst);
+    }
+
+    pub fn set(&self, now_secs: i64) {
+        self.millis.store(now_secs * 1000, Ordering::SeqCst);
+    }
+}
+
+impl Clock for MockClock {
+    fn now_secs(&self) -> i64 {
+        self.now_millis().div_euclid(1000)
+    }
+
+    fn now_millis(&self) -> i64 {
+        self.millis.load(Ordering::SeqCst)
+    }
+}
@@ -0,0 +1,149 @@
+use chrono::{DateTime, Datelike, Duration, NaiveDate, Timelike, Utc};
+
+/// A parsed standard 5-field cron expression (`minute hour day-of-month month
+/// day-of-week`), evaluated in UTC.
+///
+/// Each field accepts `*`, single values, ranges (`1-5`), steps (`*/15`,
+/// `0-30/10`) and comma-separated lists of those. Day-of-week runs 0-7 with
+/// both 0 and 7 meaning Sunday. As in Vixie cron, when both day fields are
+/// restricted a day matches if either of them does.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CronSchedule {
+    minutes: u64,
+    hours: u64,
+    days_of_month: u64,
+    months: u64,
+    days_of_week: u64,
+    any_day_of_month: bool,
+    any_day_of_week: bool,
+}
+
+/// How far ahead `next_after` searches before concluding an expression never fires
+/// (e.g. `0 0 31 2 *`).
+const MAX_YEARS_AHEAD: i32 = 5;
+
+impl CronSchedule {
+    pub fn parse(expr: &str) -> Result<Self, String> {
+        let fields: Vec<&str> = expr.split_whitespace().collect();
+        if fields.len() != 5 {
+            return Err(format!(
+                "cron expression '{}' must have 5 fields, found {}",
+                expr,
+                fields.len()
+            ));
+        }
+
+        let mut days_of_week = parse_field(fields[4], 0, 7)?;
+        // 7 is an alias for Sunday
+        if days_of_week & (1 << 7) != 0 {
+            days_of_week |= 1;
+        }
+
+        Ok(Self {
+            minutes: parse_field(fields[0], 0, 59)?,
+            hours: parse_field(fields[1], 0, 23)?,
+            days_of_month: parse_field(fields[2], 1, 31)?,
+            months: parse_field(fields[3], 1, 12)?,
+            days_of_week,
+            any_day_of_month: fields[2].starts_with('*'),
+            any_day_of_week: fields[4].starts_with('*'),
+        })
+    }
+
+    /// Returns the first matching minute strictly after `after` (Unix seconds).
+    pub fn next_after(&self, after: i64) -> Option<i64> {
+        let start = DateTime::from_timestamp(after, 0)?;
+        let mut t = start.with_second(0)?.with_nanosecond(0)? + Duration::minutes(1);
+        let last_year = start.year() + MAX_YEARS_AHEAD;
+
+        while t.year() <= last_year {
+            if !has(self.months, t.month()) {
+                t = start_of_next_month(t)?;
+            } else if !self.day_matches(t) {
+                t = start_of_day(t)? + Duration::days(1);
+            } else if !has(self.hours, t.hour()) {
+                t = t.with_minute(0)? + Duration::hours(1);
+            } else if !has(self.minutes, t.minute()) {
+                t += Duration::minutes(1);
+            } else {
+                return Some(t.timestamp());
+            }
+        }
+        None
+    }
+
+    fn day_matches(&self, t: DateTime<Utc>) -> bool {
+        let dom = has(self.days_of_month, t.day());
+        let dow = has(self.days_of_week, t.weekday().num_days_from_sunday());
+        match (self.any_day_of_month, self.any_day_of_week) {
+            (false, false) => dom || dow,
+            _ => dom && dow,
+        }
+    }
+}
+
+fn has(mask: u64, value: u32) -> bool {
+    mask & (1 << value) != 0
+}
+
+fn start_of_day(t: DateTime<Utc>) -> Option<DateTime<Utc>> {
+    Some(t.date_naive().and_hms_opt(0, 0, 0)?.and_utc())
+}
+
+fn start_of_next_month(t: DateTime<Utc>) -> Option<DateTime<Utc>> {
+    let (year, month) = match t.month() {
+        12 => (t.year() + 1, 1),
+        m => (t.year(), m + 1),
+    };
+    Some(
+        NaiveDate::from_ymd_opt(year, month, 1)?
+            .and_hms_opt(0, 0, 0)?
+            .and_utc(),
+    )
+}
+
+/// Parses one cron field into a bitmask of allowed values within `min..=max`.
+fn parse_field(field: &str, min: u32, max: u32) -> Result<u64, String> {
+    let mut mask = 0u64;
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => {
+                let step: u32 = step
+                    .parse()
+                    .map_err(|_| format!("invalid step in cron field '{}'", field))?;
+                if step == 0 {
+                    return Err(format!("step must be positive in cron field '{}'", field));
+                }
+                (range, step)
+            }
+            None => (part, 1),
+        };
+
+        let (start, end) = if range == "*" {
+            (min, max)
+        } else if let Some((a, b)) = range.split_once('-') {
+            (parse_value(a, field)?, parse_value(b, field)?)
+        } else {
+            let value = parse_value(range, field)?;
+            // `5/15` means "from 5 to the end in steps of 15"
+            (value, if part.contains('/') { max } else { value })
+        };
+
+        if start < min || end > max || start > end {
+            return Err(format!(
+                "cron field '{}' is out of range {}-{}",
+                field, min, max
+            ));
+        }
+        for value in (start..=end).step_by(step as usize) {
+            mask |= 1 << value;
+        }
+    }
+    Ok(mask)
+}
+
+fn parse_value(value: &str, field: &str) -> Result<u32, String> {
+    value
+        .parse()
+        .map_err(|_| format!("invalid value '{}' in cron field '{}'", value, field))
+}
@@ -0,0 +1,132 @@
+use crate::engine::TimePriorityEngine;
+use crate::job::Job;
+use crate::logging::RecentLogs;
+use crate::queue::QueueManager;
+use crate::worker::Worker;
+use serde_json::{Value, json};
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// Stands in for redacted payload values.
+pub const REDACTED: &str = "[redacted]";
+
+/// Gathers the scheduler's state into one JSON value for bug reports: the
+/// queued and dead-lettered jobs, engine counters, the worker's registered
+/// functions, the effective config of all three, and the lines kept in
+/// `logs`. Payload fields named in `redact` are replaced with `REDACTED` at
+/// any depth; log lines are included as they are.
+pub fn collect(
+    queue: &QueueManager,
+    engine: &TimePriorityEngine,
+    worker: &Worker,
+    logs: &RecentLogs,
+    redact: &[&str],
+) -> Value {
+    let jobs = |jobs: &[Job]| -> Vec<Value> {
+        jobs.iter()
+            .map(|job| {
+                let mut job = job.clone();
+                redact_fields(&mut job.payload, redact);
+                serde_json::to_value(job).unwrap_or(Value::Null)
+            })
+            .collect()
+    };
+    json!({
+        "generated_at": chrono::Utc::now().to_rfc3339(),
+        "queue": jobs(&queue.snapshot()),
+        "dead_letters": jobs(queue.dead_letters()),
+        "engine": {
+            "poll_interval_ms": engine.poll_interval().as_millis() as u64,
+            "wakeups": engine.wakeups(),
+            "ordering": engine.ordering_info(),
+        },
+        "worker": {
+            "functions": worker.registered_names(),
+        },
+        "config": {
+            "engine": engine.config(),
+            "queue": {
+                "capacity": queue.capacity(),
+                "persistence_enabled": queue.persistence_enabled(),
+                "outcome_limit": queue.outcome_limit(),
+                "known_functions": queue.known_functions(),
+            },
+            "worker": {
+                "threads": worker.threads(),
+                "log_sink": worker.has_log_sink(),
+            },
+        },
+        "logs": logs.lines(),
+    })
+}
+
+/// Startup wiring check: the queue's store is writable, the engine's
+/// dispatch thread is running, every queued job's function is registered
+/// with the worker, and the worker's log sink is connected. Functions are
+/// not called. Returns every problem found, not just the first.
+pub fn self_test(
+    queue: &QueueManager,
+    engine: &TimePriorityEngine,
+    worker: &Worker,
+) -> Result<(), Vec<String>> {
+    let mut problems = Vec::new();
+    if let Err(e) = queue.check_persistence() {
+        problems.push(e);
+    }
+    if !engine.is_running() {
+        problems.push("engine dispatch thread is not running".to_string());
+    }
+    let registered = worker.registered_names();
+    let mut missing: Vec<String> = queue
+        .snapshot()
+        .into_iter()
+        .map(|job| job.function)
+        .filter(|function| !registered.contains(function))
+        .collect();
+    missing.sort();
+    missing.dedup();
+    for function in missing {
+        problems.push(format!("no function registered for '{}'", function));
+    }
+    if let Err(e) = worker.check_log_sink() {
+        problems.push(e);
+    }
+
+    if problems.is_empty() {
+        Ok(())
+    } else {
+        Err(problems)
+    }
+}
+
+/// Writes `collect`'s output to `path` as pretty-printed JSON.
+pub fn dump_diagnostics(
+    path: impl AsRef<Path>,
+    queue: &QueueManager,
+    engine: &TimePriorityEngine,
+    worker: &Worker,
+    logs: &RecentLogs,
+    redact: &[&str],
+) -> io::Result<()> {
+    let dump = collect(queue, engine, worker, logs, redact);
+    fs::write(path, serde_json::to_string_pretty(&dump)?)
+}
+
+fn redact_fields(value: &mut Value, redact: &[&str]) {
+    match value {
+        Value::Object(fields) => {
+            for (key, field) in fields.iter_mut() {
+                if redact.contains(&key.as_str()) {
+                    *field = Value::String(REDACTED.to_string());
+                } else {
+                    redact_fields(field, redact);
+                }
+            }
+        }
+        Value::Array(items) => items
+            .iter_mut()
+            .for_each(|item| redact_fields(item, redact)),
+        _ => {}
+    }
+}
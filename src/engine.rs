@@ -1,31 +1,594 @@
+use crate::clock::{Clock, SystemClock};
 use crate::job::{Job, Status};
+use crate::logging::{DefaultFormatter, LogEvent, LogFormatter};
+#[cfg(feature = "metrics")]
+use crate::metrics::Metrics;
 use crate::queue::QueueManager;
-use chrono::Utc;
-use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::mpsc::Sender;
-use std::sync::{Arc, Mutex};
+use crate::time_format;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::Serialize;
+use std::cmp::Reverse;
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::{Arc, Condvar, Mutex};
 use std::thread::{self, JoinHandle};
-use std::time::Duration;
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+/// Reorders groups of due jobs that tie on execution time and priority,
+/// drawing from each group in weighted-random order by function name.
+/// Functions without an explicit weight count as weight 1.
+pub struct WeightedSelector {
+    weights: HashMap<String, u32>,
+    rng: StdRng,
+}
+
+impl WeightedSelector {
+    pub fn new(weights: HashMap<String, u32>, seed: u64) -> Self {
+        Self {
+            weights,
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+
+    /// Takes jobs in dispatch order (as returned by `pop_ready`) and shuffles
+    /// only within runs of equal execution time (to the millisecond) and
+    /// priority.
+    pub fn order(&mut self, jobs: Vec<Job>) -> Vec<Job> {
+        let mut ordered = Vec::with_capacity(jobs.len());
+        let mut group: Vec<Job> = Vec::new();
+        for job in jobs {
+            let ties = group.first().is_some_and(|g| {
+                g.execution_time == job.execution_time
+                    && g.execution_ms == job.execution_ms
+                    && g.priority == job.priority
+            });
+            if !ties {
+                self.drain_group(&mut group, &mut ordered);
+            }
+            group.push(job);
+        }
+        self.drain_group(&mut group, &mut ordered);
+        ordered
+    }
+
+    fn drain_group(&mut self, group: &mut Vec<Job>, out: &mut Vec<Job>) {
+        while !group.is_empty() {
+            let weights: Vec<u64> = group
+                .iter()
+                .map(|j| *self.weights.get(&j.function).unwrap_or(&1) as u64)
+                .collect();
+            let total: u64 = weights.iter().sum();
+            let mut idx = 0;
+            if total > 0 {
+                let mut pick = self.rng.gen_range(0..total);
+                while pick >= weights[idx] {
+                    pick -= weights[idx];
+                    idx += 1;
+                }
+            }
+            out.push(group.remove(idx));
+        }
+    }
+}
+
+/// How the engine orders the jobs that are due in a given tick.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub enum SchedulerMode {
+    /// Earliest execution time first, priority breaks ties.
+    #[default]
+    TimeFirst,
+    /// Highest priority first among due jobs, execution time breaks ties.
+    ///
+    /// A steady stream of high-priority jobs can starve low-priority ones
+    /// indefinitely in this mode, unless the engine has priority aging
+    /// enabled (`TimePriorityEngine::with_priority_aging`).
+    StrictPriority,
+}
+
+/// How due jobs from different groups share the worker, on top of the
+/// `SchedulerMode` ordering.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub enum Strategy {
+    /// Dispatch in `SchedulerMode` order, whatever the jobs' groups.
+    #[default]
+    TimePriority,
+    /// Takes due jobs round-robin by `Job::group`, each group in
+    /// `SchedulerMode` order, so a tenant with many due jobs can't make the
+    /// others wait until it is drained. Jobs without a group form one group
+    /// together.
+    FairShare,
+}
+
+impl SchedulerMode {
+    /// Sorts due jobs for this mode. `jobs` must already be in time-first
+    /// order (as returned by `pop_ready`); the sort is stable.
+    pub fn order(&self, jobs: &mut [Job]) {
+        match self {
+            SchedulerMode::TimeFirst => {}
+            SchedulerMode::StrictPriority => {
+                jobs.sort_by_key(|j| (Reverse(j.priority), j.execution_time))
+            }
+        }
+    }
+
+    /// Like `order`, but ranks jobs by `Job::effective_priority` at `now`.
+    /// Only `StrictPriority` looks at priority first, so the other modes are
+    /// unaffected: overdue jobs already come first there.
+    pub fn order_aged(&self, jobs: &mut [Job], now: i64, secs_per_level: u64) {
+        match self {
+            SchedulerMode::TimeFirst => self.order(jobs),
+            SchedulerMode::StrictPriority => jobs.sort_by_key(|j| {
+                (
+                    Reverse(j.effective_priority(now, secs_per_level)),
+                    j.execution_time,
+                )
+            }),
+        }
+    }
+}
+
+/// Round-robin state for `Strategy::FairShare`, kept across ticks so an
+/// in-flight limit that lets only a few jobs out per tick still rotates
+/// between groups instead of draining whichever group sorts first.
+#[derive(Default)]
+struct FairShare {
+    dispatched: u64,
+    last_served: HashMap<Option<String>, u64>,
+}
+
+impl FairShare {
+    /// Interleaves jobs from different groups: every group's first job, then
+    /// every group's second job, and so on. Within each round the group served
+    /// longest ago goes first; groups never served come before the rest, in
+    /// order of appearance.
+    fn order(&mut self, jobs: &mut [Job]) {
+        if jobs.is_empty() {
+            return;
+        }
+        let mut groups: HashMap<Option<&str>, (u64, usize, usize)> = HashMap::new();
+        let mut rank = HashMap::with_capacity(jobs.len());
+        for job in jobs.iter() {
+            let next_group = groups.len();
+            let group = job.group.as_deref();
+            let (served, first, taken) = groups.entry(group).or_insert_with(|| {
+                let served = self.last_served.get(&group.map(str::to_owned));
+                (served.copied().unwrap_or(0), next_group, 0)
+            });
+            rank.insert(job.id, (*taken, *served, *first));
+            *taken += 1;
+        }
+        // Held-back jobs are requeued and come due again, so a group with
+        // nothing in this batch has nothing waiting and can be forgotten
+        self.last_served
+            .retain(|group, _| groups.contains_key(&group.as_deref()));
+        jobs.sort_by_key(|j| rank[&j.id]);
+    }
+
+    /// Notes that `jobs` were dispatched, in order.
+    fn record(&mut self, jobs: &[Job]) {
+        for job in jobs {
+            self.dispatched += 1;
+            self.last_served.insert(job.group.clone(), self.dispatched);
+        }
+    }
+}
+
+/// Reorders jobs that tie on execution time and priority so the one with the
+/// smallest `estimated_duration_ms` goes first. Jobs without an estimate are
+/// treated as taking `default_estimate_ms`. The sort is stable, so jobs with
+/// equal estimates keep their incoming order.
+pub fn shortest_job_first(jobs: &mut [Job], default_estimate_ms: u64) {
+    jobs.sort_by_key(|j| {
+        (
+            j.execution_time,
+            j.execution_ms,
+            Reverse(j.priority),
+            j.estimated_duration_ms.unwrap_or(default_estimate_ms),
+        )
+    });
+}
+
+/// The ordering an engine is configured with, as reported by
+/// `TimePriorityEngine::ordering_info`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct OrderingInfo {
+    pub mode: SchedulerMode,
+    pub strategy: Strategy,
+    /// Default estimate used for shortest-job-first tie-breaking, if enabled.
+    pub shortest_job_first_default_ms: Option<u64>,
+    /// Per-function weights for tie-breaking, if enabled.
+    pub function_weights: Option<HashMap<String, u32>>,
+    pub weights_seed: Option<u64>,
+    /// Overdue seconds per priority level gained, if aging is enabled.
+    pub priority_aging_secs: Option<u64>,
+}
+
+/// The settings an engine is running with, as reported by
+/// `TimePriorityEngine::config`.
+#[derive(Debug, Clone, Serialize)]
+pub struct EngineConfig {
+    pub poll_interval_ms: u64,
+    pub ordering: OrderingInfo,
+    pub function_filter: FunctionFilter,
+    pub startup_policy: StartupPolicy,
+    pub condition_policy: ConditionPolicy,
+    pub max_in_flight: Option<usize>,
+    pub dry_run: bool,
+    pub paused: bool,
+}
+
+/// Restricts which functions an engine instance dispatches. Jobs for other
+/// functions are left in the queue, e.g. for another instance to pick up.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct FunctionFilter {
+    allow: Option<HashSet<String>>,
+    deny: HashSet<String>,
+}
+
+impl FunctionFilter {
+    pub fn permits(&self, function: &str) -> bool {
+        if self.deny.contains(function) {
+            return false;
+        }
+        self.allow
+            .as_ref()
+            .is_none_or(|allow| allow.contains(function))
+    }
+}
+
+/// Controls how jobs that were already overdue when the engine started
+/// (the backlog) are dispatched relative to jobs that come due afterwards.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub enum StartupPolicy {
+    /// Drain the backlog first, in normal comparator order.
+    #[default]
+    CatchUpFirst,
+    /// For the first `ticks` polling iterations only jobs that came due after
+    /// startup are dispatched; the backlog is released once the window ends.
+    RealTimeFirst { ticks: u32 },
+}
+
+/// A named runtime check that gates dispatch of jobs whose `condition`
+/// names it; see `TimePriorityEngine::register_condition`.
+pub type Condition = Arc<dyn Fn() -> bool + Send + Sync>;
+
+/// What the engine does with a due job whose condition doesn't hold (or
+/// names a condition that isn't registered).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum ConditionPolicy {
+    /// Put the job back to be checked again `secs` later.
+    Reschedule { secs: u64 },
+    /// Drop this run, recording it as `Cancelled` in the job's status and
+    /// history. Recurring jobs still get their next occurrence.
+    Skip,
+}
+
+impl Default for ConditionPolicy {
+    fn default() -> Self {
+        ConditionPolicy::Reschedule { secs: 60 }
+    }
+}
+
+/// Called once per dispatch iteration with the current time and how many
+/// jobs were found ready; see `TimePriorityEngine::with_on_tick`.
+pub type TickHook = Box<dyn FnMut(i64, usize) + Send>;
 
 pub struct TimePriorityEngine {
     queue: Arc<Mutex<QueueManager>>,
     worker_tx: Sender<Job>,
     is_running: Arc<AtomicBool>,
+    paused: Arc<AtomicBool>,
     handle: Mutex<Option<JoinHandle<()>>>,
+    function_weights: Option<(HashMap<String, u32>, u64)>,
+    function_filter: FunctionFilter,
+    startup_policy: StartupPolicy,
+    trigger: Option<(Arc<Mutex<Receiver<()>>>, bool)>,
+    sjf_default_estimate_ms: Option<u64>,
+    mode: SchedulerMode,
+    strategy: Strategy,
+    priority_aging_secs: Option<u64>,
+    dry_run: bool,
+    clock: Arc<dyn Clock>,
+    poll_interval_ms: Arc<AtomicU64>,
+    waker: Arc<Condvar>,
+    wakeups: Arc<AtomicU64>,
+    formatter: Arc<dyn LogFormatter>,
+    max_in_flight: Option<usize>,
+    completion_tx: Sender<Uuid>,
+    completion_rx: Arc<Mutex<Receiver<Uuid>>>,
+    on_tick: Option<Arc<Mutex<TickHook>>>,
+    conditions: HashMap<String, Condition>,
+    condition_policy: ConditionPolicy,
+    #[cfg(feature = "metrics")]
+    metrics: Option<Arc<Metrics>>,
 }
 
+/// Lets a worker tell the engine that a dispatched job has finished, freeing
+/// a slot under `TimePriorityEngine::with_max_in_flight`.
+#[derive(Clone)]
+pub struct CompletionSender {
+    tx: Sender<Uuid>,
+    queue: Arc<Mutex<QueueManager>>,
+    waker: Arc<Condvar>,
+}
+
+impl CompletionSender {
+    pub fn send(&self, id: Uuid) {
+        // Sent under the queue lock, like every other engine wakeup, so it
+        // can't slip in before the engine starts waiting
+        let _q = self.queue.lock().unwrap();
+        let _ = self.tx.send(id);
+        self.waker.notify_all();
+    }
+}
+
+/// How long to wait before retrying due jobs that were held back (disabled,
+/// filtered out, or deferred by the startup policy), and how often a
+/// triggered engine with `also_poll` fires on its own.
+pub const DEFAULT_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
 impl TimePriorityEngine {
     pub fn new(queue: Arc<Mutex<QueueManager>>, worker_tx: Sender<Job>) -> Self {
+        let waker = queue.lock().unwrap().waker();
+        let (completion_tx, completion_rx) = mpsc::channel();
         Self {
             queue,
             worker_tx,
             is_running: Arc::new(AtomicBool::new(false)),
+            paused: Arc::new(AtomicBool::new(false)),
             handle: Mutex::new(None),
+            function_weights: None,
+            function_filter: FunctionFilter::default(),
+            startup_policy: StartupPolicy::default(),
+            trigger: None,
+            sjf_default_estimate_ms: None,
+            mode: SchedulerMode::default(),
+            strategy: Strategy::default(),
+            priority_aging_secs: None,
+            dry_run: false,
+            clock: Arc::new(SystemClock),
+            poll_interval_ms: Arc::new(AtomicU64::new(DEFAULT_POLL_INTERVAL.as_millis() as u64)),
+            waker,
+            wakeups: Arc::new(AtomicU64::new(0)),
+            formatter: Arc::new(DefaultFormatter),
+            max_in_flight: None,
+            completion_tx,
+            completion_rx: Arc::new(Mutex::new(completion_rx)),
+            on_tick: None,
+            conditions: HashMap::new(),
+            condition_policy: ConditionPolicy::default(),
+            #[cfg(feature = "metrics")]
+            metrics: None,
+        }
+    }
+
+    /// Number of times the dispatch loop has woken up since creation.
+    pub fn wakeups(&self) -> u64 {
+        self.wakeups.load(Ordering::Relaxed)
+    }
+
+    pub fn with_poll_interval(self, interval: Duration) -> Self {
+        self.set_poll_interval(interval);
+        self
+    }
+
+    /// Changes the retry interval; a running engine picks it up on its next wakeup.
+    pub fn set_poll_interval(&self, interval: Duration) {
+        let ms = (interval.as_millis() as u64).max(1);
+        self.poll_interval_ms.store(ms, Ordering::Relaxed);
+    }
+
+    pub fn poll_interval(&self) -> Duration {
+        Duration::from_millis(self.poll_interval_ms.load(Ordering::Relaxed))
+    }
+
+    /// Renders the engine's dispatch lines with `formatter` instead of the
+    /// default `[Engine]` format.
+    pub fn with_log_formatter(mut self, formatter: impl LogFormatter + 'static) -> Self {
+        self.formatter = Arc::new(formatter);
+        self
+    }
+
+    pub fn with_mode(mut self, mode: SchedulerMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    pub fn with_strategy(mut self, strategy: Strategy) -> Self {
+        self.strategy = strategy;
+        self
+    }
+
+    /// Ranks due jobs by their priority plus one level per `secs_per_level`
+    /// seconds overdue, so low-priority jobs can't be starved under
+    /// `SchedulerMode::StrictPriority`.
+    pub fn with_priority_aging(mut self, secs_per_level: u64) -> Self {
+        self.priority_aging_secs = Some(secs_per_level);
+        self
+    }
+
+    /// Breaks time and priority ties shortest-job-first, using
+    /// `default_estimate_ms` for jobs without an `estimated_duration_ms`.
+    pub fn with_shortest_job_first(mut self, default_estimate_ms: u64) -> Self {
+        self.sjf_default_estimate_ms = Some(default_estimate_ms);
+        self
+    }
+
+    /// Drives dispatch from an external "tick now" channel: each `()` received
+    /// makes the engine dispatch whatever is due. When `also_poll` is false the
+    /// engine dispatches only on ticks; when true it keeps its regular timer too.
+    pub fn with_trigger(mut self, trigger_rx: Receiver<()>, also_poll: bool) -> Self {
+        self.trigger = Some((Arc::new(Mutex::new(trigger_rx)), also_poll));
+        self
+    }
+
+    pub fn with_startup_policy(mut self, policy: StartupPolicy) -> Self {
+        self.startup_policy = policy;
+        self
+    }
+
+    /// Only dispatch jobs whose function is in `functions`.
+    pub fn with_allowed_functions<I, S>(mut self, functions: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.function_filter.allow = Some(functions.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Never dispatch jobs whose function is in `functions`. Takes precedence
+    /// over the allow-list.
+    pub fn with_denied_functions<I, S>(mut self, functions: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.function_filter.deny = functions.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Dispatches jobs that tie on time and priority in weighted-random order
+    /// by function, using an RNG seeded with `seed`. Without this, ties are
+    /// dispatched in insertion order.
+    pub fn with_function_weights(mut self, weights: HashMap<String, u32>, seed: u64) -> Self {
+        self.function_weights = Some((weights, seed));
+        self
+    }
+
+    /// Calls `hook` on the dispatch thread every time the loop looks for due
+    /// jobs, with the `now` it used and the number of jobs it took, before
+    /// any are dispatched. Meant for tests and loop instrumentation, so it
+    /// should return quickly.
+    pub fn with_on_tick(mut self, hook: impl FnMut(i64, usize) + Send + 'static) -> Self {
+        self.on_tick = Some(Arc::new(Mutex::new(Box::new(hook))));
+        self
+    }
+
+    /// Registers a condition that jobs can name in `Job::condition`. It is
+    /// evaluated on the dispatch thread each time such a job comes due.
+    pub fn register_condition(
+        &mut self,
+        name: impl Into<String>,
+        condition: impl Fn() -> bool + Send + Sync + 'static,
+    ) {
+        self.conditions.insert(name.into(), Arc::new(condition));
+    }
+
+    pub fn with_condition_policy(mut self, policy: ConditionPolicy) -> Self {
+        self.condition_policy = policy;
+        self
+    }
+
+    /// Counts every dispatched job in `metrics`.
+    #[cfg(feature = "metrics")]
+    pub fn with_metrics(mut self, metrics: Arc<Metrics>) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    /// Reads the time from `clock` instead of the system clock, e.g. a
+    /// `MockClock` in tests. Sleeps still take real time, so pair a mock
+    /// clock with `with_trigger` to control when the loop runs.
+    pub fn with_clock(mut self, clock: impl Clock + 'static) -> Self {
+        self.clock = Arc::new(clock);
+        self
+    }
+
+    /// Logs `[DryRun] would dispatch ...` for each job as it comes due
+    /// instead of sending it to the worker. Jobs stay in the queue.
+    pub fn with_dry_run(mut self) -> Self {
+        self.dry_run = true;
+        self
+    }
+
+    /// Fast-forwards a copy of the queue through virtual time, from now up
+    /// to `until` (Unix seconds), and returns when each job would fire and
+    /// in what order, without sleeping or touching the real queue.
+    ///
+    /// Uses the engine's ordering and function filter, and treats every
+    /// dispatched job as succeeding so dependents follow. Runtime-only
+    /// settings (conditions, in-flight limits, pause, startup policy) are
+    /// not simulated, and disabled jobs never fire.
+    pub fn simulate(&self, until: i64) -> Vec<(i64, Uuid)> {
+        let mut sim = QueueManager::restore(self.queue.lock().unwrap().snapshot());
+        let mut selector = self
+            .function_weights
+            .clone()
+            .map(|(weights, seed)| WeightedSelector::new(weights, seed));
+        let mut fair_share = (self.strategy == Strategy::FairShare).then(FairShare::default);
+        let mut fired = Vec::new();
+        let mut now_ms = self.clock.now_millis();
+        while now_ms.div_euclid(1000) <= until {
+            let now = now_ms.div_euclid(1000);
+            let ready = sim
+                .pop_ready_filtered_ms(now_ms, |job| self.function_filter.permits(&job.function));
+            let ready = order_ready(
+                ready,
+                now,
+                selector.as_mut(),
+                self.sjf_default_estimate_ms,
+                self.mode,
+                fair_share.as_mut(),
+                self.priority_aging_secs,
+            );
+            if let Some(fair_share) = fair_share.as_mut() {
+                fair_share.record(&ready);
+            }
+            for job in &ready {
+                fired.push((now, job.id));
+                sim.record_status(job.id, Status::Success);
+                if let Some(next) = job.next_occurrence(now.max(job.execution_time)) {
+                    sim.push(job.next_run(next));
+                }
+            }
+            // Jump to the next time anything queued comes due
+            let next = sim
+                .snapshot()
+                .iter()
+                .map(|job| job.due_at_ms())
+                .filter(|&due| due > now_ms)
+                .min();
+            match next {
+                Some(next) => now_ms = next,
+                None => break,
+            }
+        }
+        fired
+    }
+
+    /// Describes how this engine orders due jobs.
+    pub fn ordering_info(&self) -> OrderingInfo {
+        OrderingInfo {
+            mode: self.mode,
+            strategy: self.strategy,
+            shortest_job_first_default_ms: self.sjf_default_estimate_ms,
+            function_weights: self.function_weights.as_ref().map(|(w, _)| w.clone()),
+            weights_seed: self.function_weights.as_ref().map(|(_, seed)| *seed),
+            priority_aging_secs: self.priority_aging_secs,
+        }
+    }
+
+    /// Describes the settings this engine is running with.
+    pub fn config(&self) -> EngineConfig {
+        EngineConfig {
+            poll_interval_ms: self.poll_interval().as_millis() as u64,
+            ordering: self.ordering_info(),
+            function_filter: self.function_filter.clone(),
+            startup_policy: self.startup_policy,
+            condition_policy: self.condition_policy,
+            max_in_flight: self.max_in_flight,
+            dry_run: self.dry_run,
+            paused: self.is_paused(),
         }
     }
 
     /// Starts the Time & Priority Engine in a background thread.
-    /// It polls the queue at a set interval for jobs that are ready to execute.
+    /// It sleeps until the earliest job is due, waking early when a job is
+    /// pushed, and blocks indefinitely while the queue is empty.
     pub fn start(&self) {
         let mut handle_lock = self.handle.lock().unwrap();
         if handle_lock.is_some() {
@@ -37,45 +600,347 @@ impl TimePriorityEngine {
         let queue_clone = Arc::clone(&self.queue);
         let tx_clone = self.worker_tx.clone();
         let running_flag = Arc::clone(&self.is_running);
+        let paused = Arc::clone(&self.paused);
+        let mut selector = self
+            .function_weights
+            .clone()
+            .map(|(weights, seed)| WeightedSelector::new(weights, seed));
+        let filter = self.function_filter.clone();
+        let startup_policy = self.startup_policy;
+        let trigger = self.trigger.clone();
+        let sjf_default_estimate_ms = self.sjf_default_estimate_ms;
+        let mode = self.mode;
+        let mut fair_share = (self.strategy == Strategy::FairShare).then(FairShare::default);
+        let priority_aging_secs = self.priority_aging_secs;
+        let poll_interval_ms = Arc::clone(&self.poll_interval_ms);
+        let waker = Arc::clone(&self.waker);
+        let wakeups = Arc::clone(&self.wakeups);
+        let formatter = Arc::clone(&self.formatter);
+        let max_in_flight = self.max_in_flight;
+        let completion_rx = Arc::clone(&self.completion_rx);
+        let on_tick = self.on_tick.clone();
+        let conditions = self.conditions.clone();
+        let condition_policy = self.condition_policy;
+        let dry_run = self.dry_run;
+        let clock = Arc::clone(&self.clock);
+        #[cfg(feature = "metrics")]
+        let metrics = self.metrics.clone();
 
         let thread_handle = thread::spawn(move || {
-            println!("[Engine] Started polling thread.");
+            println!("[Engine] Started dispatch thread.");
+            let started_at = clock.now_secs();
+            let mut tick: u32 = 0;
+            let mut in_flight: usize = 0;
+            let mut announced = HashSet::new();
             while running_flag.load(Ordering::Relaxed) {
-                let now = Utc::now().timestamp();
+                let poll_interval = Duration::from_millis(poll_interval_ms.load(Ordering::Relaxed));
+                wakeups.fetch_add(1, Ordering::Relaxed);
+                // With an external trigger, wait for a tick here instead of
+                // sleeping at the end of the loop
+                if let Some((trigger_rx, also_poll)) = &trigger {
+                    let signal = trigger_rx.lock().unwrap().recv_timeout(poll_interval);
+                    match signal {
+                        Ok(()) => {}
+                        Err(RecvTimeoutError::Timeout) if *also_poll => {}
+                        Err(RecvTimeoutError::Timeout) => continue,
+                        Err(RecvTimeoutError::Disconnected) => {
+                            thread::sleep(poll_interval);
+                            if !*also_poll {
+                                continue;
+                            }
+                        }
+                    }
+                }
+
+                let now_ms = clock.now_millis();
+                let now = now_ms.div_euclid(1000);
+                let hold_backlog = match startup_policy {
+                    StartupPolicy::CatchUpFirst => false,
+                    StartupPolicy::RealTimeFirst { ticks } => tick < ticks,
+                };
+                tick = tick.saturating_add(1);
 
                 let mut ready_jobs = Vec::new();
-                // Secure the lock briefly to extract ready jobs
-                if let Ok(mut q) = queue_clone.lock() {
-                    ready_jobs = q.pop_ready(now);
+                // Secure the lock briefly to extract ready jobs. While paused
+                // nothing is taken, so due jobs wait for `resume`.
+                if !paused.load(Ordering::SeqCst)
+                    && let Ok(mut q) = queue_clone.lock()
+                {
+                    ready_jobs = q.pop_ready_filtered_ms(now_ms, |job| {
+                        filter.permits(&job.function)
+                            && !(hold_backlog && job.execution_time < started_at)
+                    });
+                }
+                if let Some(on_tick) = &on_tick {
+                    (on_tick.lock().unwrap())(now, ready_jobs.len());
+                }
+                let condition_holds = |job: &Job| {
+                    job.condition.as_ref().is_none_or(|name| {
+                        conditions.get(name).is_some_and(|condition| condition())
+                    })
+                };
+
+                // A dry run only reports what would fire, once per job and
+                // time. Every due job goes back into the queue untouched, so
+                // none of the condition or recurrence handling below applies.
+                if dry_run {
+                    let jobs = std::mem::take(&mut ready_jobs);
+                    let would_fire = jobs.iter().filter(|job| condition_holds(job)).cloned();
+                    for job in order_ready(
+                        would_fire.collect(),
+                        now,
+                        selector.as_mut(),
+                        sjf_default_estimate_ms,
+                        mode,
+                        fair_share.as_mut(),
+                        priority_aging_secs,
+                    ) {
+                        if announced.insert((job.id, job.execution_time)) {
+                            println!(
+                                "[DryRun] would dispatch '{}' at {}",
+                                job.description,
+                                time_format::format_rfc3339(job.execution_time)
+                            );
+                        }
+                    }
+                    queue_clone.lock().unwrap().requeue(jobs);
+                }
+
+                // Jobs whose condition doesn't hold are deferred or skipped
+                let unmet: Vec<Job>;
+                (ready_jobs, unmet) = ready_jobs.into_iter().partition(condition_holds);
+                let (mut deferred, mut skipped) = (Vec::new(), Vec::new());
+                for mut job in unmet {
+                    let name = job.condition.as_deref().unwrap_or_default();
+                    match condition_policy {
+                        ConditionPolicy::Reschedule { secs } => {
+                            println!(
+                                "[Engine] Condition '{}' not met for job {}; retrying in {}s",
+                                name,
+                                job.short_id(),
+                                secs
+                            );
+                            job.execution_time = now + secs as i64;
+                            job.execution_ms = 0;
+                            deferred.push(job);
+                        }
+                        ConditionPolicy::Skip => {
+                            println!(
+                                "[Engine] Condition '{}' not met for job {}; skipping",
+                                name,
+                                job.short_id()
+                            );
+                            skipped.push(job);
+                        }
+                    }
+                }
+
+                ready_jobs = order_ready(
+                    ready_jobs,
+                    now,
+                    selector.as_mut(),
+                    sjf_default_estimate_ms,
+                    mode,
+                    fair_share.as_mut(),
+                    priority_aging_secs,
+                );
+
+                // Only dispatch up to the in-flight limit; the rest go back
+                // into the queue until workers report completions
+                in_flight =
+                    in_flight.saturating_sub(completion_rx.lock().unwrap().try_iter().count());
+                let mut held = match max_in_flight {
+                    Some(max) => {
+                        let room = max.saturating_sub(in_flight).min(ready_jobs.len());
+                        ready_jobs.split_off(room)
+                    }
+                    None => Vec::new(),
+                };
+                in_flight += ready_jobs.len();
+                if let Some(fair_share) = fair_share.as_mut() {
+                    fair_share.record(&ready_jobs);
+                }
+                held.append(&mut deferred);
+
+                if !ready_jobs.is_empty() || !held.is_empty() || !skipped.is_empty() {
+                    let mut q = queue_clone.lock().unwrap();
+                    q.requeue(held);
+                    // Recurring jobs go back in at their next occurrence. Counting
+                    // from `now` skips any occurrences missed while overdue.
+                    for job in ready_jobs.iter().chain(&skipped) {
+                        if let Some(next) = job.next_occurrence(now.max(job.execution_time)) {
+                            q.push(job.next_run(next));
+                        }
+                    }
+                    // A skipped run is finished for good, like a cancelled one
+                    for job in &mut skipped {
+                        job.status = Status::Cancelled;
+                        job.completed_at = Some(now);
+                        q.record_status(job.id, Status::Cancelled);
+                        q.append_history(job);
+                    }
                 }
 
                 // Push ready jobs to the worker channel
                 for mut job in ready_jobs {
                     job.status = Status::Running;
-                    println!(
-                        "[Engine] Job {} ('{}') is ready (priority: {}). Dispatching to worker...",
-                        job.id, job.description, job.priority
-                    );
+                    println!("{}", formatter.format(&LogEvent::Dispatched(&job)));
+                    #[cfg(feature = "metrics")]
+                    if let Some(metrics) = &metrics {
+                        metrics.record_dispatched();
+                    }
                     if let Err(e) = tx_clone.send(job) {
                         eprintln!("[Engine] Failed to dispatch job: {}", e);
                     }
                 }
 
-                // Poll every 500ms
-                thread::sleep(Duration::from_millis(500));
+                if trigger.is_none() {
+                    // Checking the flag under the queue lock pairs with `stop`,
+                    // so its wakeup can't slip in before we start waiting
+                    let q = queue_clone.lock().unwrap();
+                    if running_flag.load(Ordering::SeqCst) {
+                        let wait = if paused.load(Ordering::SeqCst) {
+                            None
+                        } else {
+                            next_wait(&q, now_ms, poll_interval, clock.as_ref())
+                        };
+                        match wait {
+                            Some(timeout) => drop(waker.wait_timeout(q, timeout)),
+                            None => drop(waker.wait(q)),
+                        }
+                    }
+                }
             }
-            println!("[Engine] Polling thread stopped gracefully.");
+            println!("[Engine] Dispatch thread stopped gracefully.");
         });
 
         *handle_lock = Some(thread_handle);
     }
 
+    /// Dispatches at most `max` jobs that haven't been reported finished
+    /// through `completion_sender`. Due jobs beyond the limit stay queued.
+    /// The worker must report every job it receives, or the engine stalls
+    /// once the limit is reached.
+    pub fn with_max_in_flight(mut self, max: usize) -> Self {
+        self.max_in_flight = Some(max);
+        self
+    }
+
+    /// Handle for reporting finished jobs back to this engine.
+    pub fn completion_sender(&self) -> CompletionSender {
+        CompletionSender {
+            tx: self.completion_tx.clone(),
+            queue: Arc::clone(&self.queue),
+            waker: Arc::clone(&self.waker),
+        }
+    }
+
+    /// Stops dispatching without stopping the thread. Jobs that come due
+    /// while paused stay queued.
+    pub fn pause(&self) {
+        self.paused.store(true, Ordering::SeqCst);
+    }
+
+    /// Resumes dispatching; anything that came due while paused is
+    /// dispatched right away.
+    pub fn resume(&self) {
+        let _q = self.queue.lock().unwrap();
+        self.paused.store(false, Ordering::SeqCst);
+        self.waker.notify_all();
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::SeqCst)
+    }
+
+    /// Whether the dispatch thread has been started and is still alive.
+    pub fn is_running(&self) -> bool {
+        self.handle
+            .lock()
+            .unwrap()
+            .as_ref()
+            .is_some_and(|handle| !handle.is_finished())
+    }
+
     /// Signals the Engine thread to stop and waits for it to finish gracefully.
     pub fn stop(&self) {
-        self.is_running.store(false, Ordering::SeqCst);
+        self.signal_stop();
         let mut handle_lock = self.handle.lock().unwrap();
         if let Some(handle) = handle_lock.take() {
             let _ = handle.join();
         }
     }
+
+    /// Like `stop`, but gives up waiting after `timeout` so a wedged dispatch
+    /// loop can't hang shutdown. Returns whether the thread finished; if not,
+    /// it is logged and left detached.
+    pub fn stop_timeout(&self, timeout: Duration) -> bool {
+        self.signal_stop();
+        let Some(handle) = self.handle.lock().unwrap().take() else {
+            return true;
+        };
+        let deadline = Instant::now() + timeout;
+        while !handle.is_finished() {
+            if Instant::now() >= deadline {
+                eprintln!(
+                    "[Engine] Dispatch thread did not stop within {:?}; leaving it detached.",
+                    timeout
+                );
+                return false;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        let _ = handle.join();
+        true
+    }
+
+    fn signal_stop(&self) {
+        let _q = self.queue.lock().unwrap();
+        self.is_running.store(false, Ordering::SeqCst);
+        self.waker.notify_all();
+    }
+}
+
+/// Applies the configured tie-breakers, scheduler mode and strategy to a
+/// batch of due jobs, which arrive in time-first order.
+fn order_ready(
+    mut jobs: Vec<Job>,
+    now: i64,
+    selector: Option<&mut WeightedSelector>,
+    sjf_default_estimate_ms: Option<u64>,
+    mode: SchedulerMode,
+    fair_share: Option<&mut FairShare>,
+    priority_aging_secs: Option<u64>,
+) -> Vec<Job> {
+    if let Some(selector) = selector {
+        jobs = selector.order(jobs);
+    }
+    if let Some(default_estimate_ms) = sjf_default_estimate_ms {
+        shortest_job_first(&mut jobs, default_estimate_ms);
+    }
+    match priority_aging_secs {
+        Some(secs) => mode.order_aged(&mut jobs, now, secs),
+        None => mode.order(&mut jobs),
+    }
+    if let Some(fair_share) = fair_share {
+        fair_share.order(&mut jobs);
+    }
+    jobs
+}
+
+/// How long the engine should sleep after a dispatch pass at `now_ms`, or
+/// `None` to wait until woken because the queue is empty.
+fn next_wait(
+    q: &QueueManager,
+    now_ms: i64,
+    poll_interval: Duration,
+    clock: &dyn Clock,
+) -> Option<Duration> {
+    let next = q.peek()?.due_at_ms();
+    if next <= now_ms {
+        // Still due after the pass, so it was held back; retry later
+        return Some(poll_interval);
+    }
+    let until_due = next - clock.now_millis();
+    Some(Duration::from_millis(until_due.max(0) as u64))
 }
@@ -1,23 +1,214 @@
+use crate::clock::{Clock, SystemClock};
+use crate::cron::CronSchedule;
+use crate::time_format;
 use serde::{Deserialize, Serialize};
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use uuid::Uuid;
 
+/// Serialized as the variant name (`"Pending"`). Deserialization also accepts
+/// the lowercase and uppercase spellings so files written by other tools load.
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum Status {
+    #[serde(alias = "pending", alias = "PENDING")]
     Pending,
+    #[serde(alias = "running", alias = "RUNNING")]
     Running,
+    #[serde(alias = "success", alias = "SUCCESS")]
     Success,
+    #[serde(alias = "failed", alias = "FAILED")]
     Failed,
+    /// Stopped by `Worker::cancel` (running) or cancelled before it ran.
+    #[serde(alias = "cancelled", alias = "CANCELLED")]
+    Cancelled,
+}
+
+/// Spelling used by `Status::to_case` for external consumers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StatusCase {
+    /// `Pending`, the serde wire format
+    Pascal,
+    /// `pending`; identical to snake_case for the current variants
+    Lower,
+    /// `PENDING`
+    Upper,
+}
+
+impl Status {
+    pub const ALL: [Status; 5] = [
+        Status::Pending,
+        Status::Running,
+        Status::Success,
+        Status::Failed,
+        Status::Cancelled,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Status::Pending => "Pending",
+            Status::Running => "Running",
+            Status::Success => "Success",
+            Status::Failed => "Failed",
+            Status::Cancelled => "Cancelled",
+        }
+    }
+
+    /// Whether a job may move from this status to `next`: Pending→Running,
+    /// Running→Success or Failed, Failed→Pending when it is retried, and
+    /// Pending or Running→Cancelled.
+    pub fn can_transition_to(&self, next: &Status) -> bool {
+        matches!(
+            (self, next),
+            (Status::Pending, Status::Running)
+                | (Status::Running, Status::Success)
+                | (Status::Running, Status::Failed)
+                | (Status::Failed, Status::Pending)
+                | (Status::Pending, Status::Cancelled)
+                | (Status::Running, Status::Cancelled)
+        )
+    }
+
+    pub fn to_case(&self, case: StatusCase) -> String {
+        match case {
+            StatusCase::Pascal => self.as_str().to_string(),
+            StatusCase::Lower => self.as_str().to_lowercase(),
+            StatusCase::Upper => self.as_str().to_uppercase(),
+        }
+    }
+}
+
+impl std::str::FromStr for Status {
+    type Err = String;
+
+    /// Parses any of the `StatusCase` spellings, case-insensitively.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Status::ALL
+            .into_iter()
+            .find(|status| status.as_str().eq_ignore_ascii_case(s))
+            .ok_or_else(|| format!("unknown status '{}'", s))
+    }
+}
+
+/// Where the worker runs a job.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Isolation {
+    /// Runs on the worker's own execution loop.
+    #[default]
+    Pooled,
+    /// Runs on a freshly spawned OS thread so a heavy job doesn't hold up the loop.
+    Dedicated,
+}
+
+/// How long a failed job waits before its next attempt.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RetryPolicy {
+    /// Retry as soon as the job is back in the queue.
+    #[default]
+    Immediate,
+    /// Wait the same delay before every retry.
+    FixedDelay(Duration),
+    /// Wait `base`, then twice that, four times that and so on, never more than `max`.
+    ExponentialBackoff { base: Duration, max: Duration },
+}
+
+impl RetryPolicy {
+    /// Delay before the given retry, counting from 1.
+    pub fn delay(&self, retry: u32) -> Duration {
+        match *self {
+            RetryPolicy::Immediate => Duration::ZERO,
+            RetryPolicy::FixedDelay(delay) => delay,
+            RetryPolicy::ExponentialBackoff { base, max } => {
+                let factor = 2u32.saturating_pow(retry.saturating_sub(1));
+                base.saturating_mul(factor).min(max)
+            }
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Job {
     pub id: Uuid,
+    /// Unix seconds. Also accepted as an RFC3339 string when loading.
+    #[serde(with = "crate::time_format::unix_or_rfc3339")]
     pub execution_time: i64,
+    /// Milliseconds past `execution_time` (0-999), for sub-second
+    /// scheduling. Files written before this existed load as 0.
+    #[serde(default)]
+    pub execution_ms: u16,
     pub priority: u8,
     pub description: String,
     pub function: String,
     pub status: Status,
+    /// Disabled jobs stay queued but are never handed out by `pop_ready`.
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    /// Insertion order assigned by `QueueManager::push`; the final tie-break
+    /// between jobs with equal time and priority.
+    #[serde(default)]
+    pub seq: u64,
+    #[serde(default)]
+    pub isolation: Isolation,
+    /// Expected run time, used by the engine's shortest-job-first ordering.
+    #[serde(default)]
+    pub estimated_duration_ms: Option<u64>,
+    /// 5-field cron expression. Recurring jobs are re-enqueued at their next
+    /// occurrence each time they are dispatched; `None` means run once.
+    #[serde(default)]
+    pub schedule: Option<String>,
+    /// Arguments handed to the registered function. Older files without a
+    /// payload load as `Value::Null`.
+    #[serde(default)]
+    pub payload: serde_json::Value,
+    /// How many times a failed run is retried before the job stays `Failed`.
+    #[serde(default)]
+    pub max_retries: u32,
+    /// Retries used so far.
+    #[serde(default)]
+    pub retry_count: u32,
+    #[serde(default)]
+    pub retry_policy: RetryPolicy,
+    /// How long the worker waits for the function before giving up on it and
+    /// failing the job. `None` waits indefinitely.
+    #[serde(default)]
+    pub timeout_secs: Option<u64>,
+    /// Jobs that must finish with `Status::Success` before this one is
+    /// dispatched.
+    #[serde(default)]
+    pub depends_on: Vec<Uuid>,
+    /// Name of a condition registered with the engine that must hold for the
+    /// job to be dispatched when due.
+    #[serde(default)]
+    pub condition: Option<String>,
+    /// Free-form labels for grouping jobs, e.g. `"nightly"` or `"customer-123"`.
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Caller-chosen key identifying the logical job. While a job with a
+    /// given key is queued, `QueueManager::push` ignores others with it.
+    #[serde(default)]
+    pub idempotency_key: Option<String>,
+    /// Tenant or other owner the job belongs to, for
+    /// `Strategy::FairShare`.
+    #[serde(default)]
+    pub group: Option<String>,
+    /// Unix seconds at which the job finished for good, set by the worker
+    /// when it records the job in the store's history. Also accepted as an
+    /// RFC3339 string when loading.
+    #[serde(default, with = "crate::time_format::unix_or_rfc3339_opt")]
+    pub completed_at: Option<i64>,
+    /// For occurrences of a recurring job after the first, the id of that
+    /// first run. Each occurrence has its own `id`.
+    #[serde(default)]
+    pub series_id: Option<Uuid>,
+    /// Set by `Worker::cancel` while the job runs. Functions that may run
+    /// for a while should poll `is_cancelled` and return early once it is
+    /// set. Not persisted; clones share the same flag.
+    #[serde(skip)]
+    pub cancel: Arc<AtomicBool>,
+}
+
+fn default_enabled() -> bool {
+    true
 }
 
 impl Job {
@@ -27,24 +218,384 @@ impl Job {
             .unwrap()
             .as_secs() as i64
     }
+
+    /// Priority raised by one level for every `secs_per_level` seconds the
+    /// job has been overdue at `now`, so waiting jobs eventually outrank
+    /// fresher high-priority ones. The stored `priority` is not changed.
+    pub fn effective_priority(&self, now: i64, secs_per_level: u64) -> u32 {
+        let overdue = now.saturating_sub(self.execution_time).max(0) as u64;
+        let boost = overdue / secs_per_level.max(1);
+        (self.priority as u32).saturating_add(boost.min(u32::MAX as u64) as u32)
+    }
+
+    /// Whether `Worker::cancel` has been called for this run of the job.
+    pub fn is_cancelled(&self) -> bool {
+        self.cancel.load(Ordering::SeqCst)
+    }
+
+    /// The first 8 hex digits of the id, for display only. Lookups must
+    /// always use the full `id`.
+    pub fn short_id(&self) -> String {
+        self.id.simple().to_string()[..8].to_string()
+    }
+
+    fn validate_execution_time(execution_time: i64, now: i64) -> Result<(), String> {
+        if execution_time < now {
+            return Err(format!("execution_time {} is in the past", execution_time));
+        }
+        Ok(())
+    }
     pub fn new(
         execution_time: i64,
         priority: u8,
         description: impl Into<String>,
         function: impl Into<String>,
     ) -> Result<Job, String> {
-        if execution_time < Self::now() {
-            return Err(format!("execution_time {} is in the past", execution_time));
-        }
+        Self::new_with_clock(
+            execution_time,
+            priority,
+            description,
+            function,
+            &SystemClock,
+        )
+    }
+
+    /// Like `new`, but due at an RFC3339 datetime such as
+    /// `"2026-06-01T09:00:00Z"` or `"2026-06-01T11:00:00+02:00"`. Times
+    /// without an explicit offset are rejected as ambiguous.
+    pub fn new_at(
+        datetime: &str,
+        priority: u8,
+        description: impl Into<String>,
+        function: impl Into<String>,
+    ) -> Result<Job, String> {
+        let execution_time = time_format::parse_rfc3339(datetime)?;
+        Self::new(execution_time, priority, description, function)
+    }
+
+    /// Like `new`, but due after a delay such as `"1h30m"`, parsed by
+    /// `time_format::parse_duration`.
+    pub fn new_in(
+        delay: &str,
+        priority: u8,
+        description: impl Into<String>,
+        function: impl Into<String>,
+    ) -> Result<Job, String> {
+        let secs = time_format::parse_duration(delay)?.as_secs();
+        let execution_time = i64::try_from(secs)
+            .ok()
+            .and_then(|secs| Self::now().checked_add(secs))
+            .ok_or_else(|| format!("delay '{}' is too large", delay))?;
+        Self::new(execution_time, priority, description, function)
+    }
 
-        Ok(Self {
+    /// Like `new`, but checks `execution_time` against `clock` rather than
+    /// the system time.
+    pub fn new_with_clock(
+        execution_time: i64,
+        priority: u8,
+        description: impl Into<String>,
+        function: impl Into<String>,
+        clock: &dyn Clock,
+    ) -> Result<Job, String> {
+        Self::validate_execution_time(execution_time, clock.now_secs())?;
+        Ok(Self::build(execution_time, priority, description, function))
+    }
+
+    fn build(
+        execution_time: i64,
+        priority: u8,
+        description: impl Into<String>,
+        function: impl Into<String>,
+    ) -> Job {
+        Self {
             id: Uuid::new_v4(),
             execution_time,
+            execution_ms: 0,
             priority,
             description: description.into(),
             function: function.into(),
             status: Status::Pending,
-        })
+            enabled: true,
+            seq: 0,
+            isolation: Isolation::Pooled,
+            estimated_duration_ms: None,
+            schedule: None,
+            payload: serde_json::Value::Null,
+            max_retries: 0,
+            retry_count: 0,
+            retry_policy: RetryPolicy::Immediate,
+            timeout_secs: None,
+            depends_on: Vec::new(),
+            condition: None,
+            tags: Vec::new(),
+            idempotency_key: None,
+            group: None,
+            completed_at: None,
+            series_id: None,
+            cancel: Arc::default(),
+        }
+    }
+
+    /// Like `new`, but due at `at_ms` Unix milliseconds, e.g. to fire in
+    /// 250ms rather than on a whole second.
+    pub fn new_at_millis(
+        at_ms: i64,
+        priority: u8,
+        description: impl Into<String>,
+        function: impl Into<String>,
+    ) -> Result<Job, String> {
+        Self::new_at_millis_with_clock(at_ms, priority, description, function, &SystemClock)
+    }
+
+    /// Like `new_at_millis`, but checks `at_ms` against `clock` rather than
+    /// the system time.
+    pub fn new_at_millis_with_clock(
+        at_ms: i64,
+        priority: u8,
+        description: impl Into<String>,
+        function: impl Into<String>,
+        clock: &dyn Clock,
+    ) -> Result<Job, String> {
+        if at_ms < clock.now_millis() {
+            return Err(format!("execution time {}ms is in the past", at_ms));
+        }
+        let mut job = Self::build(at_ms.div_euclid(1000), priority, description, function);
+        job.execution_ms = at_ms.rem_euclid(1000) as u16;
+        Ok(job)
+    }
+
+    /// When the job is due, in Unix milliseconds.
+    pub fn due_at_ms(&self) -> i64 {
+        self.execution_time * 1000 + self.execution_ms as i64
+    }
+
+    /// A job due right now, for running a function outside the queue.
+    pub(crate) fn immediate(function: &str, payload: serde_json::Value) -> Job {
+        Self::build(Self::now(), 0, function, function).with_payload(payload)
+    }
+
+    pub fn with_payload(mut self, payload: serde_json::Value) -> Job {
+        self.payload = payload;
+        self
+    }
+
+    pub fn with_max_retries(mut self, max_retries: u32) -> Job {
+        self.max_retries = max_retries;
+        self
+    }
+
+    pub fn with_timeout_secs(mut self, timeout_secs: u64) -> Job {
+        self.timeout_secs = Some(timeout_secs);
+        self
+    }
+
+    pub fn with_dependencies(mut self, ids: impl IntoIterator<Item = Uuid>) -> Job {
+        self.depends_on = ids.into_iter().collect();
+        self
+    }
+
+    pub fn with_tags<I, S>(mut self, tags: I) -> Job
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.tags = tags.into_iter().map(Into::into).collect();
+        self
+    }
+
+    pub fn with_idempotency_key(mut self, key: impl Into<String>) -> Job {
+        self.idempotency_key = Some(key.into());
+        self
+    }
+
+    pub fn with_group(mut self, group: impl Into<String>) -> Job {
+        self.group = Some(group.into());
+        self
+    }
+
+    pub fn with_condition(mut self, condition: impl Into<String>) -> Job {
+        self.condition = Some(condition.into());
+        self
+    }
+
+    pub fn with_retry_policy(mut self, policy: RetryPolicy) -> Job {
+        self.retry_policy = policy;
+        self
+    }
+
+    /// Records a failed run. If retries remain, uses one, resets the job to
+    /// `Pending` and moves its execution time out by the retry policy's delay
+    /// so it can be re-enqueued; otherwise marks it `Failed`. Returns whether
+    /// the job should run again.
+    pub fn fail_and_retry(&mut self) -> bool {
+        if self.retry_count < self.max_retries {
+            self.retry_count += 1;
+            let delay = self.retry_policy.delay(self.retry_count);
+            // Execution times are whole seconds; never retry early
+            self.execution_time = Self::now() + delay.as_millis().div_ceil(1000) as i64;
+            self.execution_ms = 0;
+            self.status = Status::Pending;
+            true
+        } else {
+            self.status = Status::Failed;
+            false
+        }
+    }
+
+    /// Makes this a recurring job following the given cron expression.
+    pub fn with_schedule(mut self, schedule: impl Into<String>) -> Result<Job, String> {
+        let schedule = schedule.into();
+        CronSchedule::parse(&schedule)?;
+        self.schedule = Some(schedule);
+        Ok(self)
+    }
+
+    /// The occurrence of this recurring job due at `at`: a fresh job with
+    /// its own id and retry budget, linked to earlier runs by `series_id`.
+    /// The idempotency key stays with the submitted run, so a retry of it
+    /// can't clash with the next occurrence.
+    pub fn next_run(&self, at: i64) -> Job {
+        let mut next = self.clone();
+        next.id = Uuid::new_v4();
+        next.series_id = Some(self.series_id.unwrap_or(self.id));
+        next.execution_time = at;
+        next.execution_ms = 0;
+        next.status = Status::Pending;
+        next.retry_count = 0;
+        next.completed_at = None;
+        next.idempotency_key = None;
+        next.cancel = Arc::default();
+        next
+    }
+
+    /// The next fire time strictly after `after`, or `None` for one-shot jobs
+    /// (and for expressions that never match).
+    pub fn next_occurrence(&self, after: i64) -> Option<i64> {
+        let schedule = CronSchedule::parse(self.schedule.as_deref()?).ok()?;
+        schedule.next_after(after)
+    }
+}
+
+/// Longest `JobSpec::description` accepted, in bytes.
+pub const MAX_DESCRIPTION_LEN: usize = 1024;
+
+/// Largest `JobSpec::payload` accepted, in bytes of serialized JSON.
+pub const MAX_PAYLOAD_BYTES: usize = 64 * 1024;
+
+/// Why `JobSpec::validate` rejected a spec. Serializes as
+/// `{"kind": "payload_too_large", "detail": {"len": ..., "max": ...}}`, with
+/// no `detail` for variants that carry nothing.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", content = "detail", rename_all = "snake_case")]
+pub enum SpecError {
+    /// `execution_time` is before now.
+    ExecutionTimeInPast(i64),
+    EmptyFunction,
+    /// The description is `len` bytes, over `MAX_DESCRIPTION_LEN`.
+    DescriptionTooLong {
+        len: usize,
+        max: usize,
+    },
+    /// The serialized payload is `len` bytes, over `MAX_PAYLOAD_BYTES`.
+    PayloadTooLarge {
+        len: usize,
+        max: usize,
+    },
+    /// The function isn't known to whoever validated the spec.
+    UnknownFunction(String),
+}
+
+impl std::fmt::Display for SpecError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SpecError::ExecutionTimeInPast(time) => {
+                write!(f, "execution_time {} is in the past", time)
+            }
+            SpecError::EmptyFunction => write!(f, "function must not be empty"),
+            SpecError::DescriptionTooLong { len, max } => write!(
+                f,
+                "description is {} bytes, more than the {} allowed",
+                len, max
+            ),
+            SpecError::PayloadTooLarge { len, max } => {
+                write!(f, "payload is {} bytes, more than the {} allowed", len, max)
+            }
+            SpecError::UnknownFunction(function) => {
+                write!(f, "no function registered for '{}'", function)
+            }
+        }
+    }
+}
+
+impl std::error::Error for SpecError {}
+
+/// The creation fields of a job, as accepted from external input such as an
+/// import file. Unlike a persisted `Job` it carries no id or status.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JobSpec {
+    #[serde(with = "crate::time_format::unix_or_rfc3339")]
+    pub execution_time: i64,
+    pub priority: u8,
+    pub description: String,
+    pub function: String,
+    #[serde(default)]
+    pub payload: serde_json::Value,
+    #[serde(default)]
+    pub max_retries: u32,
+    #[serde(default)]
+    pub idempotency_key: Option<String>,
+}
+
+impl JobSpec {
+    /// Runs the same checks as job creation without building a job, plus
+    /// the description and payload size limits.
+    pub fn validate(&self) -> Result<(), SpecError> {
+        if Job::validate_execution_time(self.execution_time, Job::now()).is_err() {
+            return Err(SpecError::ExecutionTimeInPast(self.execution_time));
+        }
+        if self.function.trim().is_empty() {
+            return Err(SpecError::EmptyFunction);
+        }
+        if self.description.len() > MAX_DESCRIPTION_LEN {
+            return Err(SpecError::DescriptionTooLong {
+                len: self.description.len(),
+                max: MAX_DESCRIPTION_LEN,
+            });
+        }
+        let payload_len = serde_json::to_vec(&self.payload).map_or(0, |bytes| bytes.len());
+        if payload_len > MAX_PAYLOAD_BYTES {
+            return Err(SpecError::PayloadTooLarge {
+                len: payload_len,
+                max: MAX_PAYLOAD_BYTES,
+            });
+        }
+        Ok(())
+    }
+
+    /// Like `validate`, and also rejects a function `is_known` returns
+    /// false for.
+    pub fn validate_with(&self, is_known: impl Fn(&str) -> bool) -> Result<(), SpecError> {
+        self.validate()?;
+        if !is_known(&self.function) {
+            return Err(SpecError::UnknownFunction(self.function.clone()));
+        }
+        Ok(())
+    }
+
+    /// Builds a `Job` from this spec, applying the same validation as `Job::new`.
+    pub fn into_job(self) -> Result<Job, String> {
+        self.validate().map_err(|e| e.to_string())?;
+        let mut job = Job::new(
+            self.execution_time,
+            self.priority,
+            self.description,
+            self.function,
+        )?
+        .with_payload(self.payload)
+        .with_max_retries(self.max_retries);
+        job.idempotency_key = self.idempotency_key;
+        Ok(job)
     }
 }
 
@@ -67,6 +618,8 @@ impl Ord for Job {
         other
             .execution_time
             .cmp(&self.execution_time)
+            .then(other.execution_ms.cmp(&self.execution_ms))
             .then(self.priority.cmp(&other.priority))
+            .then(other.seq.cmp(&self.seq))
     }
 }
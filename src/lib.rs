@@ -1,4 +1,16 @@
+#[cfg(feature = "api")]
+pub mod api;
+pub mod clock;
+pub mod cron;
+pub mod diagnostics;
 pub mod engine;
 pub mod job;
+pub mod logging;
+#[cfg(feature = "metrics")]
+pub mod metrics;
+pub mod persistence;
 pub mod queue;
+#[cfg(feature = "sqlite")]
+pub mod sqlite_store;
+pub mod time_format;
 pub mod worker;
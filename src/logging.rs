@@ -0,0 +1,97 @@
+use crate::job::Job;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+/// Something the engine or worker reports as a human-readable log line.
+#[derive(Debug, Clone, Copy)]
+pub enum LogEvent<'a> {
+    /// The engine handed a due job to the worker channel.
+    Dispatched(&'a Job),
+    /// The worker is about to call the job's function.
+    Executing(&'a Job),
+    /// The job's function returned an error.
+    Failed { job: &'a Job, error: &'a str },
+    /// The job's function panicked.
+    Panicked { job: &'a Job, message: &'a str },
+    /// The job's function didn't return within its timeout.
+    TimedOut { job: &'a Job, secs: u64 },
+    /// A failed job was pushed back onto the queue.
+    Retrying(&'a Job),
+}
+
+/// Renders `LogEvent`s to the lines printed by the engine and worker.
+pub trait LogFormatter: Send + Sync {
+    fn format(&self, event: &LogEvent) -> String;
+}
+
+/// The built-in `[Engine]` / `[Worker]` prefixed format.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DefaultFormatter;
+
+impl LogFormatter for DefaultFormatter {
+    fn format(&self, event: &LogEvent) -> String {
+        match event {
+            LogEvent::Dispatched(job) => format!(
+                "[Engine] Job {} ('{}') is ready (priority: {}). Dispatching to worker...",
+                job.short_id(),
+                job.description,
+                job.priority
+            ),
+            LogEvent::Executing(job) => format!("[Worker] Executing: {}", job.function),
+            LogEvent::Failed { job, error } => {
+                format!("[Worker] Error: '{}' failed: {}", job.function, error)
+            }
+            LogEvent::Panicked { job, message } => {
+                format!("[Worker] Error: '{}' panicked: {}", job.function, message)
+            }
+            LogEvent::TimedOut { job, secs } => {
+                format!("[Worker] Job {} timed out after {}s", job.id, secs)
+            }
+            LogEvent::Retrying(job) => format!(
+                "[Worker] Retrying '{}' ({}/{})",
+                job.function, job.retry_count, job.max_retries
+            ),
+        }
+    }
+}
+
+/// How many lines `RecentLogs::default` keeps.
+pub const DEFAULT_RECENT_LOG_LINES: usize = 200;
+
+/// Keeps the last few log lines, e.g. for `diagnostics::collect`. Clones
+/// share the same buffer, so one can be fed from the log sink while another
+/// is read.
+#[derive(Debug, Clone)]
+pub struct RecentLogs {
+    lines: Arc<Mutex<VecDeque<String>>>,
+    limit: usize,
+}
+
+impl RecentLogs {
+    pub fn new(limit: usize) -> Self {
+        Self {
+            lines: Arc::new(Mutex::new(VecDeque::new())),
+            limit,
+        }
+    }
+
+    /// Appends `line`, dropping the oldest once past the limit.
+    pub fn push(&self, line: impl Into<String>) {
+        let mut lines = self.lines.lock().unwrap();
+        lines.push_back(line.into());
+        while lines.len() > self.limit {
+            lines.pop_front();
+        }
+    }
+
+    /// The kept lines, oldest first.
+    pub fn lines(&self) -> Vec<String> {
+        self.lines.lock().unwrap().iter().cloned().collect()
+    }
+}
+
+impl Default for RecentLogs {
+    fn default() -> Self {
+        Self::new(DEFAULT_RECENT_LOG_LINES)
+    }
+}
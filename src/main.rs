@@ -1,27 +1,125 @@
+use scheduler::diagnostics;
 use scheduler::engine::TimePriorityEngine;
 use scheduler::job::Job;
+use scheduler::logging::RecentLogs;
+use scheduler::persistence::PersistenceManager;
 use scheduler::queue::QueueManager;
+use scheduler::worker::{self, Worker};
+use std::io::Read;
 use std::sync::{Arc, Mutex, mpsc};
 use std::thread;
 use std::time::Duration;
 
+/// The path passed via `--dump-diagnostics <path>`, if any.
+fn diagnostics_path() -> Option<String> {
+    let args: Vec<String> = std::env::args().collect();
+    let pos = args.iter().position(|a| a == "--dump-diagnostics")?;
+    let path = args.get(pos + 1).cloned();
+    if path.is_none() {
+        eprintln!("--dump-diagnostics requires a file path");
+    }
+    path
+}
+
+/// Reads the job specs passed via `--import <path>`, where `-` means stdin.
+fn read_import_source() -> Option<String> {
+    let args: Vec<String> = std::env::args().collect();
+    let pos = args.iter().position(|a| a == "--import")?;
+    let Some(path) = args.get(pos + 1) else {
+        eprintln!("--import requires a file path (or '-' for stdin)");
+        return None;
+    };
+
+    let result = if path == "-" {
+        let mut buf = String::new();
+        std::io::stdin().read_to_string(&mut buf).map(|_| buf)
+    } else {
+        std::fs::read_to_string(path)
+    };
+    match result {
+        Ok(contents) => Some(contents),
+        Err(e) => {
+            eprintln!("Failed to read import source '{}': {}", path, e);
+            None
+        }
+    }
+}
+
 fn main() {
     println!("Initializing Scheduler Component...");
 
-    let queue = Arc::new(Mutex::new(QueueManager::new()));
+    // With --require-persistence, an unwritable store aborts startup instead
+    // of running without durability
+    let require_persistence = std::env::args().any(|a| a == "--require-persistence");
+    let store = Box::new(PersistenceManager::new("queue.json"));
+    // A jobs file that exists but can't be loaded also aborts, so it isn't
+    // overwritten by an empty queue
+    let queue = if require_persistence {
+        QueueManager::with_required_store(store)
+    } else {
+        QueueManager::with_store(store)
+    };
+    let queue = match queue {
+        Ok(queue) => queue,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+    let queue = Arc::new(Mutex::new(queue));
+
+    if let Some(input) = read_import_source() {
+        let report = queue.lock().unwrap().import_specs(&input);
+        println!(
+            "Imported {} job(s), {} failed.",
+            report.imported,
+            report.failed()
+        );
+        for (line, err) in &report.errors {
+            eprintln!("  line {}: {}", line, err);
+        }
+    }
     // Channel from the Time & Priority Engine to the Worker Executor
     let (tx, rx) = mpsc::channel();
 
     let engine = TimePriorityEngine::new(Arc::clone(&queue), tx);
     engine.start();
 
-    // Start a simple worker simulation thread
+    // Start the worker on a supervised thread, restarted if it ever dies
+    let (log_tx, log_rx) = mpsc::channel();
+    let mut worker = Worker::new()
+        .with_log_sink(log_tx)
+        .with_queue(Arc::clone(&queue));
+    let functions: [(&str, worker::JobFn); 3] = [
+        ("backup_fn", worker::backup_db),
+        ("email_fn", worker::send_email),
+        ("hotfix_fn", worker::apply_hotfix),
+    ];
+    for (name, f) in functions {
+        if let Err(e) = worker.register(name, f) {
+            eprintln!("[Main] {}", e);
+        }
+    }
+    let recent_logs = RecentLogs::default();
+    let logs = recent_logs.clone();
     thread::spawn(move || {
-        while let Ok(job) = rx.recv() {
-            println!("[Worker] Executing job {} ('{}')", job.id, job.description);
-            thread::sleep(Duration::from_millis(50)); // Simulating work
+        for line in log_rx {
+            println!("{}", line);
+            logs.push(line);
         }
     });
+    let supervisor = worker.clone().start_supervised(rx);
+
+    // With --self-test, broken wiring aborts startup with a report
+    if std::env::args().any(|a| a == "--self-test")
+        && let Err(problems) = diagnostics::self_test(&queue.lock().unwrap(), &engine, &worker)
+    {
+        eprintln!("Self-test failed:");
+        for problem in &problems {
+            eprintln!("  {}", problem);
+        }
+        std::process::exit(1);
+    }
 
     // Schedule some jobs
     let now = chrono::Utc::now().timestamp();
@@ -47,6 +145,25 @@ fn main() {
     thread::sleep(Duration::from_secs(4));
     println!("Scheduler simulation complete. Shutting down.");
 
-    // Stop the engine gracefully
+    // With --dump-diagnostics, write the final state for bug reports
+    if let Some(path) = diagnostics_path() {
+        let result = diagnostics::dump_diagnostics(
+            &path,
+            &queue.lock().unwrap(),
+            &engine,
+            &worker,
+            &recent_logs,
+            &[],
+        );
+        if let Err(e) = result {
+            eprintln!("Failed to write diagnostics to '{}': {}", path, e);
+        }
+    }
+
+    // Stop the engine gracefully, then close the job channel and let the
+    // worker finish whatever it is running
     engine.stop();
+    drop(engine);
+    let _ = supervisor.join();
+    worker.shutdown();
 }
@@ -0,0 +1,100 @@
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+/// Upper bounds, in seconds, of the job duration histogram buckets.
+pub const DURATION_BUCKETS: [f64; 11] = [
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+/// Counters shared by the engine and worker, rendered in the Prometheus text
+/// exposition format by `render` (and served at `GET /metrics` by the API).
+#[derive(Debug, Default)]
+pub struct Metrics {
+    dispatched: AtomicU64,
+    succeeded: AtomicU64,
+    failed: AtomicU64,
+    /// Non-cumulative per-bucket counts; `render` accumulates them
+    duration_buckets: [AtomicU64; DURATION_BUCKETS.len()],
+    duration_sum_us: AtomicU64,
+    duration_count: AtomicU64,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record_dispatched(&self) {
+        self.dispatched.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_succeeded(&self) {
+        self.succeeded.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Counts a failed run, including ones that will be retried.
+    pub fn record_failed(&self) {
+        self.failed.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn observe_duration(&self, duration: Duration) {
+        let secs = duration.as_secs_f64();
+        if let Some(bucket) = DURATION_BUCKETS.iter().position(|&le| secs <= le) {
+            self.duration_buckets[bucket].fetch_add(1, Ordering::Relaxed);
+        }
+        self.duration_sum_us
+            .fetch_add(duration.as_micros() as u64, Ordering::Relaxed);
+        self.duration_count.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Renders every metric, with `queue_depth` (usually `QueueManager::len`)
+    /// as the `scheduler_queue_depth` gauge.
+    pub fn render(&self, queue_depth: usize) -> String {
+        let mut out = String::new();
+        let counters = [
+            (
+                "scheduler_jobs_dispatched_total",
+                "Jobs handed to the worker by the engine.",
+                &self.dispatched,
+            ),
+            (
+                "scheduler_jobs_succeeded_total",
+                "Job runs that succeeded.",
+                &self.succeeded,
+            ),
+            (
+                "scheduler_jobs_failed_total",
+                "Job runs that failed, panicked or timed out.",
+                &self.failed,
+            ),
+        ];
+        for (name, help, value) in counters {
+            let _ = writeln!(out, "# HELP {} {}", name, help);
+            let _ = writeln!(out, "# TYPE {} counter", name);
+            let _ = writeln!(out, "{} {}", name, value.load(Ordering::Relaxed));
+        }
+
+        let _ = writeln!(
+            out,
+            "# HELP scheduler_queue_depth Jobs waiting in the queue."
+        );
+        let _ = writeln!(out, "# TYPE scheduler_queue_depth gauge");
+        let _ = writeln!(out, "scheduler_queue_depth {}", queue_depth);
+
+        let name = "scheduler_job_duration_seconds";
+        let _ = writeln!(out, "# HELP {} Time spent running each job.", name);
+        let _ = writeln!(out, "# TYPE {} histogram", name);
+        let mut cumulative = 0;
+        for (le, count) in DURATION_BUCKETS.iter().zip(&self.duration_buckets) {
+            cumulative += count.load(Ordering::Relaxed);
+            let _ = writeln!(out, "{}_bucket{{le=\"{}\"}} {}", name, le, cumulative);
+        }
+        let count = self.duration_count.load(Ordering::Relaxed);
+        let sum = self.duration_sum_us.load(Ordering::Relaxed) as f64 / 1_000_000.0;
+        let _ = writeln!(out, "{}_bucket{{le=\"+Inf\"}} {}", name, count);
+        let _ = writeln!(out, "{}_sum {}", name, sum);
+        let _ = writeln!(out, "{}_count {}", name, count);
+        out
+    }
+}
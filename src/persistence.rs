@@ -0,0 +1,360 @@
+use crate::job::Job;
+use crate::time_format::{self, TimeFormat};
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write as _};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+/// Storage backend for the queue's jobs.
+pub trait JobStore: Send {
+    /// Returns every stored job. A store that doesn't exist yet yields an
+    /// empty vec; one that exists but can't be read or parsed is an error.
+    fn load(&self) -> io::Result<Vec<Job>>;
+    /// Replaces the stored jobs with `jobs`.
+    fn save(&self, jobs: &[Job]) -> io::Result<()>;
+    /// Records a job that has finished for good (succeeded, failed with no
+    /// retries left, or cancelled), keeping earlier entries. Stores without
+    /// a history ignore it.
+    fn append_history(&self, _job: &Job) -> io::Result<()> {
+        Ok(())
+    }
+    /// The last `limit` jobs recorded by `append_history`, oldest first.
+    fn load_history(&self, _limit: usize) -> Vec<Job> {
+        Vec::new()
+    }
+}
+
+/// Persists jobs as a JSON array in a single file, written atomically via a
+/// temporary file and rename. Times load from either Unix seconds or RFC3339
+/// strings, whichever format they were written in.
+pub struct PersistenceManager {
+    path: PathBuf,
+    time_format: TimeFormat,
+}
+
+impl PersistenceManager {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            time_format: TimeFormat::default(),
+        }
+    }
+
+    /// Writes `execution_time` in `format`, e.g. RFC3339 for files that
+    /// operators read and edit by hand.
+    pub fn with_time_format(mut self, format: TimeFormat) -> Self {
+        self.time_format = format;
+        self
+    }
+
+    pub fn path(&self) -> &PathBuf {
+        &self.path
+    }
+
+    /// Where finished jobs are appended, one JSON object per line: the
+    /// jobs file with a `.history.jsonl` extension.
+    pub fn history_path(&self) -> PathBuf {
+        self.path.with_extension("history.jsonl")
+    }
+}
+
+impl JobStore for PersistenceManager {
+    fn load(&self) -> io::Result<Vec<Job>> {
+        let contents = match fs::read_to_string(&self.path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => {
+                return Err(io::Error::new(
+                    e.kind(),
+                    format!("failed to read {}: {}", self.path.display(), e),
+                ));
+            }
+        };
+        serde_json::from_str(&contents).map_err(|e| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("failed to parse {}: {}", self.path.display(), e),
+            )
+        })
+    }
+
+    fn save(&self, jobs: &[Job]) -> io::Result<()> {
+        let json = match self.time_format {
+            TimeFormat::Unix => serde_json::to_string_pretty(jobs)?,
+            TimeFormat::Rfc3339 => {
+                let values = jobs
+                    .iter()
+                    .map(with_rfc3339_times)
+                    .collect::<Result<Vec<_>, _>>()?;
+                serde_json::to_string_pretty(&values)?
+            }
+        };
+        let tmp = self.path.with_extension("json.tmp");
+        fs::write(&tmp, json)?;
+        fs::rename(&tmp, &self.path)
+    }
+
+    fn append_history(&self, job: &Job) -> io::Result<()> {
+        let mut line = match self.time_format {
+            TimeFormat::Unix => serde_json::to_string(job)?,
+            TimeFormat::Rfc3339 => with_rfc3339_times(job)?.to_string(),
+        };
+        line.push('\n');
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.history_path())?
+            .write_all(line.as_bytes())
+    }
+
+    /// Unparseable lines are skipped with a warning.
+    fn load_history(&self, limit: usize) -> Vec<Job> {
+        let path = self.history_path();
+        let contents = match fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Vec::new(),
+            Err(e) => {
+                eprintln!("[Persistence] Failed to read {}: {}", path.display(), e);
+                return Vec::new();
+            }
+        };
+        let jobs: Vec<Job> = contents
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .filter_map(|line| match serde_json::from_str(line) {
+                Ok(job) => Some(job),
+                Err(e) => {
+                    eprintln!("[Persistence] Skipping bad history line: {}", e);
+                    None
+                }
+            })
+            .collect();
+        jobs[jobs.len().saturating_sub(limit)..].to_vec()
+    }
+}
+
+/// Serializes `job` with its times (`execution_time`, `completed_at`) as
+/// RFC3339 strings rather than Unix seconds.
+fn with_rfc3339_times(job: &Job) -> serde_json::Result<serde_json::Value> {
+    let mut value = serde_json::to_value(job)?;
+    value["execution_time"] = time_format::format_rfc3339(job.execution_time).into();
+    if let Some(completed_at) = job.completed_at {
+        value["completed_at"] = time_format::format_rfc3339(completed_at).into();
+    }
+    Ok(value)
+}
+
+/// How long `DebouncedStore` collects snapshots before writing the latest.
+pub const DEFAULT_DEBOUNCE_WINDOW: Duration = Duration::from_millis(250);
+
+enum Write {
+    Save(Vec<Job>),
+    /// Write whatever is pending now and report how that went
+    Flush(Sender<io::Result<()>>),
+}
+
+/// Wraps another store so that a burst of saves turns into one write: each
+/// snapshot is handed to a background thread, which waits `window` after the
+/// first one and writes only the last snapshot it received by then. Saves
+/// therefore always succeed; write errors are logged by the thread.
+/// Dropping the store writes any pending snapshot before returning.
+pub struct DebouncedStore<S: JobStore + Sync + 'static> {
+    inner: Arc<S>,
+    tx: Option<Sender<Write>>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl<S: JobStore + Sync + 'static> DebouncedStore<S> {
+    pub fn new(inner: S, window: Duration) -> Self {
+        let inner = Arc::new(inner);
+        let (tx, rx) = mpsc::channel();
+        let writer = Arc::clone(&inner);
+        let handle = thread::spawn(move || {
+            let write = |jobs: &[Job]| {
+                let result = writer.save(jobs);
+                if let Err(e) = &result {
+                    eprintln!("[Persistence] Failed to write debounced snapshot: {}", e);
+                }
+                result
+            };
+            let mut pending: Option<Vec<Job>> = None;
+            let mut deadline = Instant::now();
+            loop {
+                let next = match pending {
+                    Some(_) => rx.recv_timeout(deadline.saturating_duration_since(Instant::now())),
+                    None => rx.recv().map_err(|_| RecvTimeoutError::Disconnected),
+                };
+                match next {
+                    Ok(Write::Save(jobs)) => {
+                        if pending.is_none() {
+                            deadline = Instant::now() + window;
+                        }
+                        pending = Some(jobs);
+                    }
+                    Ok(Write::Flush(done)) => {
+                        let result = pending.take().map_or(Ok(()), |jobs| write(&jobs));
+                        let _ = done.send(result);
+                    }
+                    Err(RecvTimeoutError::Timeout) => {
+                        if let Some(jobs) = pending.take() {
+                            let _ = write(&jobs);
+                        }
+                    }
+                    Err(RecvTimeoutError::Disconnected) => {
+                        if let Some(jobs) = pending.take() {
+                            let _ = write(&jobs);
+                        }
+                        return;
+                    }
+                }
+            }
+        });
+        Self {
+            inner,
+            tx: Some(tx),
+            handle: Some(handle),
+        }
+    }
+
+    /// Writes the pending snapshot, if any, and waits for it to land.
+    pub fn flush(&self) {
+        let (done_tx, done_rx) = mpsc::channel();
+        if let Some(tx) = &self.tx
+            && tx.send(Write::Flush(done_tx)).is_ok()
+        {
+            let _ = done_rx.recv();
+        }
+    }
+
+    /// Saves `jobs` without waiting out the window and returns once they are
+    /// written, with the write's result. Gives up with `TimedOut` after
+    /// `timeout`, in which case the write may still land later.
+    pub fn save_and_wait(&self, jobs: &[Job], timeout: Duration) -> io::Result<()> {
+        let stopped = || io::Error::other("debounced writer has stopped");
+        let tx = self.tx.as_ref().ok_or_else(stopped)?;
+        let (done_tx, done_rx) = mpsc::channel();
+        tx.send(Write::Save(jobs.to_vec())).map_err(|_| stopped())?;
+        tx.send(Write::Flush(done_tx)).map_err(|_| stopped())?;
+        match done_rx.recv_timeout(timeout) {
+            Ok(result) => result,
+            Err(RecvTimeoutError::Timeout) => Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                "snapshot was not written in time",
+            )),
+            Err(RecvTimeoutError::Disconnected) => Err(stopped()),
+        }
+    }
+
+    pub fn inner(&self) -> &S {
+        &self.inner
+    }
+}
+
+impl<S: JobStore + Sync + 'static> JobStore for DebouncedStore<S> {
+    /// Flushes first, so a load never misses a snapshot still in the window.
+    fn load(&self) -> io::Result<Vec<Job>> {
+        self.flush();
+        self.inner.load()
+    }
+
+    fn save(&self, jobs: &[Job]) -> io::Result<()> {
+        match &self.tx {
+            Some(tx) => tx
+                .send(Write::Save(jobs.to_vec()))
+                .map_err(|_| io::Error::other("debounced writer has stopped")),
+            None => self.inner.save(jobs),
+        }
+    }
+
+    /// History entries don't overwrite each other, so they go straight to
+    /// the inner store.
+    fn append_history(&self, job: &Job) -> io::Result<()> {
+        self.inner.append_history(job)
+    }
+
+    fn load_history(&self, limit: usize) -> Vec<Job> {
+        self.inner.load_history(limit)
+    }
+}
+
+impl<S: JobStore + Sync + 'static> Drop for DebouncedStore<S> {
+    fn drop(&mut self) {
+        drop(self.tx.take());
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+/// Keeps jobs in memory only. Intended for tests, so they don't touch the filesystem.
+#[derive(Default)]
+pub struct InMemoryStore {
+    jobs: Mutex<Vec<Job>>,
+    saves: AtomicUsize,
+    history: Mutex<Vec<Job>>,
+}
+
+impl InMemoryStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Starts the store with jobs already in it, as if saved by an earlier run.
+    pub fn with_jobs(jobs: Vec<Job>) -> Self {
+        Self {
+            jobs: Mutex::new(jobs),
+            saves: AtomicUsize::new(0),
+            history: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// How many times `save` has been called, for asserting write counts.
+    pub fn save_count(&self) -> usize {
+        self.saves.load(Ordering::SeqCst)
+    }
+}
+
+impl JobStore for InMemoryStore {
+    fn load(&self) -> io::Result<Vec<Job>> {
+        Ok(self.jobs.lock().unwrap().clone())
+    }
+
+    fn save(&self, jobs: &[Job]) -> io::Result<()> {
+        *self.jobs.lock().unwrap() = jobs.to_vec();
+        self.saves.fetch_add(1, Ordering::SeqCst);
+        Ok(())
+    }
+
+    fn append_history(&self, job: &Job) -> io::Result<()> {
+        self.history.lock().unwrap().push(job.clone());
+        Ok(())
+    }
+
+    fn load_history(&self, limit: usize) -> Vec<Job> {
+        let history = self.history.lock().unwrap();
+        history[history.len().saturating_sub(limit)..].to_vec()
+    }
+}
+
+/// Lets a caller keep a handle on a store it has handed to the queue.
+impl<T: JobStore + Sync> JobStore for Arc<T> {
+    fn load(&self) -> io::Result<Vec<Job>> {
+        (**self).load()
+    }
+
+    fn save(&self, jobs: &[Job]) -> io::Result<()> {
+        (**self).save(jobs)
+    }
+
+    fn append_history(&self, job: &Job) -> io::Result<()> {
+        (**self).append_history(job)
+    }
+
+    fn load_history(&self, limit: usize) -> Vec<Job> {
+        (**self).load_history(limit)
+    }
+}
@@ -1,10 +1,144 @@
-use crate::job::{Job, Status};
-use std::collections::BinaryHeap;
+use crate::clock::{Clock, SystemClock};
+use crate::job::{Job, JobSpec, Status};
+use crate::persistence::JobStore;
+use std::collections::{BinaryHeap, HashMap, HashSet};
+use std::sync::{Arc, Condvar};
 use uuid::Uuid;
 
-#[derive(Default)]
+/// Why `QueueManager::try_push` refused a job.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PushError {
+    /// The queue already holds `capacity` jobs.
+    Full { capacity: usize },
+    /// Another queued job, `existing`, holds the same idempotency key.
+    Duplicate { key: String, existing: Uuid },
+    /// The job names a function missing from `set_known_functions`.
+    UnknownFunction(String),
+    /// The job depends on itself, directly or through queued jobs.
+    DependencyCycle(Uuid),
+}
+
+impl std::fmt::Display for PushError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PushError::Full { capacity } => write!(f, "queue is full ({} jobs)", capacity),
+            PushError::Duplicate { key, existing } => write!(
+                f,
+                "idempotency key '{}' is already held by queued job {}",
+                key, existing
+            ),
+            PushError::UnknownFunction(function) => {
+                write!(f, "no function registered for '{}'", function)
+            }
+            PushError::DependencyCycle(id) => write!(f, "job {} has a dependency cycle", id),
+        }
+    }
+}
+
+impl std::error::Error for PushError {}
+
+/// Outcome of `QueueManager::import_specs`.
+#[derive(Debug, Default)]
+pub struct ImportReport {
+    pub imported: usize,
+    /// 1-based line (JSONL) or element (JSON array) number, with the reason it was rejected.
+    pub errors: Vec<(usize, String)>,
+}
+
+impl ImportReport {
+    pub fn failed(&self) -> usize {
+        self.errors.len()
+    }
+}
+
+/// What `QueueManager::check_functions` does with jobs whose function isn't known.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnknownFunctionPolicy {
+    /// Leave them queued and enabled, waiting for the function to be registered.
+    KeepPending,
+    /// Leave them queued but disabled so they are never dispatched.
+    Disable,
+    /// Move them out of the queue into the dead-letter list.
+    DeadLetter,
+}
+
+/// Heap entry for a queued job: only the fields that decide dispatch order.
+/// The job itself lives in `QueueManager::jobs`, so it can be looked up and
+/// changed in place. An entry whose `seq` no longer matches the indexed job
+/// (because the job was removed or re-pushed) is stale and skipped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct HeapKey {
+    execution_time: i64,
+    execution_ms: u16,
+    priority: u8,
+    seq: u64,
+    id: Uuid,
+}
+
+impl HeapKey {
+    fn of(job: &Job) -> Self {
+        Self {
+            execution_time: job.execution_time,
+            execution_ms: job.execution_ms,
+            priority: job.priority,
+            seq: job.seq,
+            id: job.id,
+        }
+    }
+}
+
+/// Same order as `Job`: earliest time, then highest priority, then lowest seq.
+impl Ord for HeapKey {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        other
+            .execution_time
+            .cmp(&self.execution_time)
+            .then(other.execution_ms.cmp(&self.execution_ms))
+            .then(self.priority.cmp(&other.priority))
+            .then(other.seq.cmp(&self.seq))
+    }
+}
+
+impl PartialOrd for HeapKey {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// How many jobs' outcomes a queue keeps for dependency checks before
+/// forgetting those no queued job depends on.
+pub const DEFAULT_OUTCOME_LIMIT: usize = 10_000;
+
 pub struct QueueManager {
-    heap: BinaryHeap<Job>,
+    heap: BinaryHeap<HeapKey>,
+    /// Every queued job by id; the heap only orders them.
+    jobs: HashMap<Uuid, Job>,
+    next_seq: u64,
+    store: Option<Box<dyn JobStore>>,
+    dead_letters: Vec<Job>,
+    waker: Arc<Condvar>,
+    /// Set by `set_persistence_enabled(false)`; mutations skip the store until cleared.
+    persistence_paused: bool,
+    /// Last known status of jobs that have left the queue, for dependency
+    /// checks, with the order it was recorded in. Kept in memory only, and
+    /// trimmed past `outcome_limit`.
+    outcomes: HashMap<Uuid, (Status, u64)>,
+    outcomes_recorded: u64,
+    outcome_limit: usize,
+    /// Most jobs `try_push` will accept; `None` is unbounded.
+    capacity: Option<usize>,
+    /// Idempotency key to the queued job holding it. Entries can go stale
+    /// like heap entries; `queued_with_key` checks them against `jobs`.
+    keys: HashMap<String, Uuid>,
+    /// When set, jobs naming any other function are refused on push.
+    known_functions: Option<Arc<HashSet<String>>>,
+    clock: Arc<dyn Clock>,
+}
+
+impl Default for QueueManager {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 #[allow(dead_code)]
@@ -12,70 +146,780 @@ impl QueueManager {
     pub fn new() -> Self {
         QueueManager {
             heap: BinaryHeap::new(),
+            jobs: HashMap::new(),
+            next_seq: 0,
+            store: None,
+            dead_letters: Vec::new(),
+            waker: Arc::new(Condvar::new()),
+            persistence_paused: false,
+            outcomes: HashMap::new(),
+            outcomes_recorded: 0,
+            outcome_limit: DEFAULT_OUTCOME_LIMIT,
+            capacity: None,
+            keys: HashMap::new(),
+            known_functions: None,
+            clock: Arc::new(SystemClock),
         }
     }
 
-    pub fn push(&mut self, job: Job) {
-        self.heap.push(job);
+    /// Creates an empty queue whose `try_push` rejects jobs once `max` are
+    /// queued. `push` is not limited.
+    pub fn with_capacity(max: usize) -> Self {
+        let mut queue = Self::new();
+        queue.capacity = Some(max);
+        queue
     }
 
-    pub fn pop(&mut self) -> Option<Job> {
-        self.heap.pop()
+    /// Sets or clears the limit enforced by `try_push`, e.g. on a queue made
+    /// with `with_store`. Jobs already queued are kept.
+    pub fn set_capacity(&mut self, max: Option<usize>) {
+        self.capacity = max;
     }
 
-    pub fn remove(&mut self, id: Uuid) -> Option<Job> {
-        let mut all: Vec<Job> = self.heap.drain().collect();
-        let pos = all.iter().position(|j| j.id == id);
-        match pos {
-            Some(i) => {
-                let removed = all.remove(i);
-                self.heap = BinaryHeap::from(all);
-                Some(removed)
+    pub fn capacity(&self) -> Option<usize> {
+        self.capacity
+    }
+
+    /// The functions jobs are checked against on push, sorted, if set.
+    pub fn known_functions(&self) -> Option<Vec<String>> {
+        self.known_functions.as_ref().map(|known| {
+            let mut names: Vec<String> = known.iter().cloned().collect();
+            names.sort();
+            names
+        })
+    }
+
+    /// Uses `clock` instead of the system time, e.g. a `MockClock` shared
+    /// with the engine in tests.
+    pub fn set_clock(&mut self, clock: Arc<dyn Clock>) {
+        self.clock = clock;
+    }
+
+    /// Sets or clears the functions jobs may name, typically the worker's
+    /// `registered_names`. While set, pushing a job with any other function
+    /// is refused up front instead of failing when it is dispatched. Jobs
+    /// already queued are kept; see `check_functions` for those.
+    pub fn set_known_functions(&mut self, known: Option<Arc<HashSet<String>>>) {
+        self.known_functions = known;
+    }
+
+    /// Checks `job`'s function against the set given to
+    /// `set_known_functions`. Always succeeds when none is set.
+    pub fn validate_function(&self, job: &Job) -> Result<(), String> {
+        if self.knows_function(&job.function) {
+            Ok(())
+        } else {
+            Err(format!("no function registered for '{}'", job.function))
+        }
+    }
+
+    /// Whether jobs naming `function` would get past `validate_function`.
+    pub fn knows_function(&self, function: &str) -> bool {
+        self.known_functions
+            .as_ref()
+            .is_none_or(|known| known.contains(function))
+    }
+
+    /// Creates a queue backed by `store`: previously saved jobs are loaded
+    /// and every mutation is written back. Fails if the store exists but
+    /// can't be loaded, rather than starting empty and overwriting it on the
+    /// first push.
+    pub fn with_store(store: Box<dyn JobStore>) -> Result<Self, String> {
+        let jobs = store
+            .load()
+            .map_err(|e| format!("could not load saved jobs: {}", e))?;
+        let mut queue = Self::restore(jobs);
+        queue.store = Some(store);
+        Ok(queue)
+    }
+
+    /// Like `with_store`, but writes the loaded jobs straight back and fails
+    /// if that write does, for deployments that must not run without
+    /// durable storage.
+    pub fn with_required_store(store: Box<dyn JobStore>) -> Result<Self, String> {
+        let queue = Self::with_store(store)?;
+        if let Some(store) = &queue.store {
+            store
+                .save(&queue.snapshot())
+                .map_err(|e| format!("persistence is required but unavailable: {}", e))?;
+        }
+        Ok(queue)
+    }
+
+    /// Rebuilds a queue from previously saved jobs, keeping their original
+    /// insertion sequence so ties dispatch in the same order as before the
+    /// restart. New pushes continue numbering after the highest restored seq.
+    pub fn restore(jobs: Vec<Job>) -> Self {
+        let next_seq = jobs.iter().map(|j| j.seq + 1).max().unwrap_or(0);
+        QueueManager {
+            heap: jobs.iter().map(HeapKey::of).collect(),
+            keys: Self::index_keys(jobs.iter()),
+            jobs: jobs.into_iter().map(|j| (j.id, j)).collect(),
+            next_seq,
+            store: None,
+            dead_letters: Vec::new(),
+            waker: Arc::new(Condvar::new()),
+            persistence_paused: false,
+            outcomes: HashMap::new(),
+            outcomes_recorded: 0,
+            outcome_limit: DEFAULT_OUTCOME_LIMIT,
+            capacity: None,
+            known_functions: None,
+            clock: Arc::new(SystemClock),
+        }
+    }
+
+    /// Returns a copy of every queued job in dispatch order.
+    pub fn snapshot(&self) -> Vec<Job> {
+        let mut jobs: Vec<Job> = self.jobs.values().cloned().collect();
+        jobs.sort_by(|a, b| b.cmp(a));
+        jobs
+    }
+
+    /// Returns copies of the queued jobs matching `pred`, in no particular order.
+    pub fn filter(&self, pred: impl Fn(&Job) -> bool) -> Vec<Job> {
+        self.jobs.values().filter(|j| pred(j)).cloned().collect()
+    }
+
+    /// Returns copies of the queued jobs with the given status, in no particular order.
+    pub fn jobs_by_status(&self, status: Status) -> Vec<Job> {
+        self.filter(|j| j.status == status)
+    }
+
+    /// Returns copies of the queued jobs tagged `tag`, in no particular order.
+    pub fn jobs_with_tag(&self, tag: &str) -> Vec<Job> {
+        self.filter(|j| j.tags.iter().any(|t| t == tag))
+    }
+
+    /// Removes every queued job tagged `tag` with a single persistence write.
+    /// Returns how many were removed.
+    pub fn remove_by_tag(&mut self, tag: &str) -> usize {
+        let before = self.jobs.len();
+        self.jobs.retain(|_, j| !j.tags.iter().any(|t| t == tag));
+        let removed = before - self.jobs.len();
+        if removed > 0 {
+            self.prune();
+            self.notify_persistence();
+        }
+        removed
+    }
+
+    /// Condvar notified whenever a job is added or re-enabled. Engines wait on it
+    /// together with the mutex guarding this queue.
+    pub fn waker(&self) -> Arc<Condvar> {
+        Arc::clone(&self.waker)
+    }
+
+    /// Stops (or resumes) writing to the backing store. While disabled the
+    /// queue keeps working in memory only; re-enabling writes the current
+    /// jobs once so the store catches up on everything that changed.
+    pub fn set_persistence_enabled(&mut self, enabled: bool) {
+        let resumed = enabled && self.persistence_paused;
+        self.persistence_paused = !enabled;
+        if resumed {
+            self.notify_persistence();
+        }
+    }
+
+    pub fn persistence_enabled(&self) -> bool {
+        !self.persistence_paused
+    }
+
+    /// Writes the current jobs to the backing store, if there is one and
+    /// persistence isn't paused.
+    fn notify_persistence(&self) {
+        if self.persistence_paused {
+            return;
+        }
+        if let Some(store) = &self.store
+            && let Err(e) = store.save(&self.snapshot())
+        {
+            eprintln!("[Queue] Failed to persist jobs: {}", e);
+        }
+    }
+
+    /// Adds a job that has left the queue for good to the store's history,
+    /// unless persistence is paused.
+    pub fn append_history(&self, job: &Job) {
+        if self.persistence_paused {
+            return;
+        }
+        if let Some(store) = &self.store
+            && let Err(e) = store.append_history(job)
+        {
+            eprintln!(
+                "[Queue] Failed to record job {} in history: {}",
+                job.short_id(),
+                e
+            );
+        }
+    }
+
+    /// The last `limit` finished jobs in the store's history, oldest first.
+    /// Empty without a store.
+    pub fn history(&self, limit: usize) -> Vec<Job> {
+        self.store
+            .as_ref()
+            .map_or_else(Vec::new, |store| store.load_history(limit))
+    }
+
+    /// Writes the current jobs to the store right away, even while
+    /// persistence is paused, and reports whether that worked. Succeeds
+    /// trivially without a store.
+    pub fn check_persistence(&self) -> Result<(), String> {
+        match &self.store {
+            Some(store) => store
+                .save(&self.snapshot())
+                .map_err(|e| format!("persistence is not writable: {}", e)),
+            None => Ok(()),
+        }
+    }
+
+    /// Adds `job`, or replaces the queued job with the same id. A job whose
+    /// `idempotency_key` is already held by another queued job is dropped
+    /// instead, so producers can safely resubmit; returns whether it was
+    /// queued. A key can be used again once its job has left the queue.
+    /// Jobs naming an unknown function (see `set_known_functions`) or
+    /// depending on themselves are refused with a warning.
+    pub fn push(&mut self, job: Job) -> bool {
+        if let Err(e) = self.admit(&job) {
+            eprintln!(
+                "[Queue] Rejected job {} ('{}'): {}",
+                job.short_id(),
+                job.description,
+                e
+            );
+            return false;
+        }
+        self.enqueue(job);
+        true
+    }
+
+    /// The checks every new or edited job must pass, apart from capacity.
+    fn admit(&self, job: &Job) -> Result<(), PushError> {
+        if self.validate_function(job).is_err() {
+            return Err(PushError::UnknownFunction(job.function.clone()));
+        }
+        if let Some(key) = &job.idempotency_key
+            && let Some(existing) = self.queued_with_key(key)
+            && existing != job.id
+        {
+            return Err(PushError::Duplicate {
+                key: key.clone(),
+                existing,
+            });
+        }
+        if self.creates_cycle(job) {
+            return Err(PushError::DependencyCycle(job.id));
+        }
+        Ok(())
+    }
+
+    fn enqueue(&mut self, job: Job) {
+        self.insert(job);
+        self.notify_persistence();
+        self.waker.notify_all();
+    }
+
+    /// Id of the queued job holding idempotency key `key`, if any.
+    pub fn queued_with_key(&self, key: &str) -> Option<Uuid> {
+        let id = *self.keys.get(key)?;
+        let job = self.jobs.get(&id)?;
+        (job.idempotency_key.as_deref() == Some(key)).then_some(id)
+    }
+
+    fn index_keys<'a>(jobs: impl Iterator<Item = &'a Job>) -> HashMap<String, Uuid> {
+        jobs.filter_map(|j| Some((j.idempotency_key.clone()?, j.id)))
+            .collect()
+    }
+
+    fn forget_key(&mut self, job: &Job) {
+        if let Some(key) = &job.idempotency_key
+            && self.keys.get(key) == Some(&job.id)
+        {
+            self.keys.remove(key);
+        }
+    }
+
+    /// Like `push`, but reports why a job wasn't queued, and also refuses
+    /// new jobs once the queue holds `capacity` jobs so producers can back
+    /// off. Replacing a queued job by id is always allowed since it doesn't
+    /// grow the queue.
+    pub fn try_push(&mut self, job: Job) -> Result<(), PushError> {
+        self.check_capacity(&job)?;
+        self.admit(&job)?;
+        self.enqueue(job);
+        Ok(())
+    }
+
+    fn check_capacity(&self, job: &Job) -> Result<(), PushError> {
+        match self.capacity {
+            Some(capacity) if self.jobs.len() >= capacity && !self.jobs.contains_key(&job.id) => {
+                Err(PushError::Full { capacity })
             }
-            None => {
-                self.heap = BinaryHeap::from(all);
-                None
+            _ => Ok(()),
+        }
+    }
+
+    /// Like `push`, but reports why a job was refused, e.g. for depending
+    /// on itself directly or through the queued jobs it depends on.
+    pub fn push_checked(&mut self, job: Job) -> Result<(), String> {
+        self.admit(&job).map_err(|e| e.to_string())?;
+        self.enqueue(job);
+        Ok(())
+    }
+
+    fn creates_cycle(&self, job: &Job) -> bool {
+        let mut stack = job.depends_on.clone();
+        let mut seen = HashSet::new();
+        while let Some(id) = stack.pop() {
+            if id == job.id {
+                return true;
+            }
+            if seen.insert(id)
+                && let Some(next) = self.jobs.get(&id)
+            {
+                stack.extend(next.depends_on.iter().copied());
+            }
+        }
+        false
+    }
+
+    fn insert(&mut self, mut job: Job) {
+        job.seq = self.next_seq;
+        self.next_seq += 1;
+        self.index(job);
+    }
+
+    /// Adds a job to the heap and the index, keeping its current seq.
+    fn index(&mut self, job: Job) {
+        self.heap.push(HeapKey::of(&job));
+        if let Some(key) = &job.idempotency_key {
+            self.keys.insert(key.clone(), job.id);
+        }
+        if self.jobs.insert(job.id, job).is_some() {
+            // The replaced job's heap entry is now stale
+            self.prune();
+        }
+    }
+
+    fn is_live(&self, key: &HeapKey) -> bool {
+        self.jobs.get(&key.id).is_some_and(|j| j.seq == key.seq)
+    }
+
+    /// Drops stale entries from the top of the heap, so `peek` always sees a
+    /// queued job, and compacts the heap once stale entries outnumber live ones.
+    fn prune(&mut self) {
+        if self.heap.len() > 2 * self.jobs.len() + 16 {
+            self.heap = self.jobs.values().map(HeapKey::of).collect();
+            self.keys = Self::index_keys(self.jobs.values());
+            return;
+        }
+        while let Some(key) = self.heap.peek() {
+            if self.is_live(key) {
+                break;
             }
+            self.heap.pop();
         }
     }
 
+    /// Removes and returns the job at the top of the heap.
+    fn take_next(&mut self) -> Option<Job> {
+        let key = self.heap.pop()?;
+        let job = self.jobs.remove(&key.id);
+        if let Some(job) = &job {
+            self.forget_key(job);
+        }
+        self.prune();
+        job
+    }
+
+    /// Swaps the whole queue for `jobs` in one step, with a single
+    /// persistence write. Jobs already dispatched are not affected.
+    pub fn replace_all(&mut self, jobs: Vec<Job>) {
+        self.heap.clear();
+        self.jobs.clear();
+        self.keys.clear();
+        for job in jobs {
+            self.insert(job);
+        }
+        self.notify_persistence();
+        self.waker.notify_all();
+    }
+
+    pub fn pop(&mut self) -> Option<Job> {
+        let job = self.take_next();
+        if job.is_some() {
+            self.notify_persistence();
+        }
+        job
+    }
+
+    pub fn remove(&mut self, id: Uuid) -> Option<Job> {
+        let removed = self.jobs.remove(&id)?;
+        self.forget_key(&removed);
+        self.prune();
+        self.notify_persistence();
+        Some(removed)
+    }
+
     pub fn peek(&self) -> Option<&Job> {
-        self.heap.peek()
+        self.heap.peek().and_then(|key| self.jobs.get(&key.id))
+    }
+
+    /// Records the status of a job that has left the queue (typically the
+    /// result reported by the worker) and wakes anything waiting on it.
+    pub fn record_status(&mut self, id: Uuid, status: Status) {
+        self.set_outcome(id, status);
+        self.trim_outcomes();
+        self.waker.notify_all();
     }
 
+    fn set_outcome(&mut self, id: Uuid, status: Status) {
+        self.outcomes_recorded += 1;
+        self.outcomes.insert(id, (status, self.outcomes_recorded));
+    }
+
+    /// Sets how many outcomes are kept before finished jobs that no queued
+    /// job depends on are forgotten (`DEFAULT_OUTCOME_LIMIT` by default). A
+    /// job pushed later that depends on a forgotten one waits forever, so
+    /// keep this well above the number of jobs finishing between a job and
+    /// its dependents being queued. If running jobs alone still exceed the
+    /// limit, the longest-running are forgotten too, so jobs whose result is
+    /// never reported can't pile up.
+    pub fn set_outcome_limit(&mut self, limit: usize) {
+        self.outcome_limit = limit;
+        self.trim_outcomes();
+    }
+
+    pub fn outcome_limit(&self) -> usize {
+        self.outcome_limit
+    }
+
+    fn trim_outcomes(&mut self) {
+        if self.outcomes.len() <= self.outcome_limit {
+            return;
+        }
+        let needed: HashSet<Uuid> = self
+            .jobs
+            .values()
+            .flat_map(|j| j.depends_on.iter().copied())
+            .collect();
+        self.outcomes
+            .retain(|id, (status, _)| *status == Status::Running || needed.contains(id));
+        let excess = self.outcomes.len().saturating_sub(self.outcome_limit);
+        if excess > 0 {
+            let mut stale: Vec<(u64, Uuid)> = self
+                .outcomes
+                .iter()
+                .filter(|(id, _)| !needed.contains(id))
+                .map(|(&id, &(_, recorded))| (recorded, id))
+                .collect();
+            stale.sort_unstable();
+            for (_, id) in stale.into_iter().take(excess) {
+                self.outcomes.remove(&id);
+            }
+        }
+    }
+
+    /// Status of any job this queue has seen: the last recorded outcome for
+    /// jobs that were dispatched, otherwise the queued job's own status.
+    /// Outcomes of finished jobs may be forgotten; see `set_outcome_limit`.
+    pub fn status_of(&self, id: Uuid) -> Option<Status> {
+        if let Some((status, _)) = self.outcomes.get(&id) {
+            return Some(status.clone());
+        }
+        self.jobs.get(&id).map(|j| j.status.clone())
+    }
+
+    fn dependencies_met(&self, job: &Job) -> bool {
+        job.depends_on
+            .iter()
+            .all(|id| matches!(self.outcomes.get(id), Some((Status::Success, _))))
+    }
+
+    /// Pops every enabled job whose execution time has been reached and
+    /// whose dependencies have succeeded, in dispatch order: by time, then
+    /// priority, then insertion. Jobs due at the same instant always come out
+    /// together in that order. Other due jobs are left in the queue untouched.
+    /// `now` is Unix seconds, so a job due later in that second (see
+    /// `Job::execution_ms`) isn't ready yet.
     pub fn pop_ready(&mut self, now: i64) -> Vec<Job> {
+        self.pop_ready_filtered(now, |_| true)
+    }
+
+    /// Like `pop_ready`, but returns at most `limit` jobs. When the limit
+    /// cuts through a group of jobs due at the same instant, the rest of the
+    /// group stays at the front of the queue and is returned first by the
+    /// next call, ahead of anything due later.
+    pub fn pop_ready_limited(&mut self, now: i64, limit: usize) -> Vec<Job> {
+        self.take_ready(now.saturating_mul(1000), limit, |_| true)
+    }
+
+    /// Like `pop_ready`, but due jobs rejected by `accept` stay in the queue.
+    pub fn pop_ready_filtered(&mut self, now: i64, accept: impl Fn(&Job) -> bool) -> Vec<Job> {
+        self.pop_ready_filtered_ms(now.saturating_mul(1000), accept)
+    }
+
+    /// Like `pop_ready_filtered`, but at `now_ms` Unix milliseconds.
+    pub fn pop_ready_filtered_ms(
+        &mut self,
+        now_ms: i64,
+        accept: impl Fn(&Job) -> bool,
+    ) -> Vec<Job> {
+        self.take_ready(now_ms, usize::MAX, accept)
+    }
+
+    fn take_ready(&mut self, now_ms: i64, limit: usize, accept: impl Fn(&Job) -> bool) -> Vec<Job> {
         let mut ready = Vec::new();
-        while let Some(job) = self.peek() {
-            if job.execution_time <= now {
-                ready.push(self.pop().unwrap());
+        let mut held = Vec::new();
+        while ready.len() < limit
+            && let Some(job) = self.peek()
+        {
+            if job.due_at_ms() <= now_ms {
+                let job = self.take_next().unwrap();
+                if job.enabled && self.dependencies_met(&job) && accept(&job) {
+                    self.set_outcome(job.id, Status::Running);
+                    ready.push(job);
+                } else {
+                    held.push(job);
+                }
             } else {
                 break;
             }
         }
+        for job in held {
+            self.index(job);
+        }
+        if !ready.is_empty() {
+            self.notify_persistence();
+        }
         ready
     }
 
-    pub fn update_status(&mut self, id: Uuid, new_status: Status) -> bool {
-        let mut all: Vec<Job> = self.heap.drain().collect();
-        let found = all.iter_mut().find(|j| j.id == id);
-        match found {
-            Some(job) => {
-                job.status = new_status;
-                self.heap = BinaryHeap::from(all);
-                true
+    /// Puts back jobs taken by `pop_ready` that couldn't be dispatched after
+    /// all, keeping their original place among equal-time jobs.
+    pub fn requeue(&mut self, jobs: Vec<Job>) {
+        if jobs.is_empty() {
+            return;
+        }
+        for job in jobs {
+            self.outcomes.remove(&job.id);
+            self.index(job);
+        }
+        self.notify_persistence();
+    }
+
+    /// Replaces the queued job `id` with `edited`, for correcting a job's
+    /// fields without losing it. The id, status and retry count are kept from
+    /// the queued job. Fails if `id` isn't queued or the edited job would
+    /// be refused by `push`, e.g. for naming an unknown function.
+    pub fn update_job(&mut self, id: Uuid, mut edited: Job) -> Result<(), String> {
+        let Some(current) = self.jobs.get(&id) else {
+            return Err(format!("job {} is not queued", id));
+        };
+        edited.id = id;
+        edited.status = current.status.clone();
+        edited.retry_count = current.retry_count;
+        self.admit(&edited).map_err(|e| e.to_string())?;
+        // A fresh seq keeps the old heap entry from matching the new job
+        self.enqueue(edited);
+        Ok(())
+    }
+
+    pub fn set_enabled(&mut self, id: Uuid, enabled: bool) -> bool {
+        let Some(job) = self.jobs.get_mut(&id) else {
+            return false;
+        };
+        job.enabled = enabled;
+        self.notify_persistence();
+        self.waker.notify_all();
+        true
+    }
+
+    /// Changes a queued job's status in place; the status doesn't affect
+    /// dispatch order, so the heap is left alone. Fails if the job isn't
+    /// queued or the change isn't allowed by `Status::can_transition_to`.
+    pub fn update_status(&mut self, id: Uuid, new_status: Status) -> Result<(), String> {
+        let Some(job) = self.jobs.get_mut(&id) else {
+            return Err(format!("job {} is not queued", id));
+        };
+        if !job.status.can_transition_to(&new_status) {
+            return Err(format!(
+                "job {} cannot move from {} to {}",
+                id,
+                job.status.as_str(),
+                new_status.as_str()
+            ));
+        }
+        job.status = new_status;
+        self.notify_persistence();
+        Ok(())
+    }
+
+    /// Parses job specs from either a JSON array or JSONL (one spec per line),
+    /// validates each one and pushes the valid jobs. Entries that are invalid
+    /// or that `try_push` would refuse are reported individually and do not
+    /// stop the import.
+    pub fn import_specs(&mut self, input: &str) -> ImportReport {
+        let mut report = ImportReport::default();
+
+        let entries: Vec<(usize, Result<JobSpec, String>)> = if input.trim_start().starts_with('[')
+        {
+            match serde_json::from_str::<Vec<serde_json::Value>>(input) {
+                Ok(values) => values
+                    .into_iter()
+                    .enumerate()
+                    .map(|(i, v)| (i + 1, serde_json::from_value(v).map_err(|e| e.to_string())))
+                    .collect(),
+                Err(e) => {
+                    report.errors.push((0, e.to_string()));
+                    return report;
+                }
+            }
+        } else {
+            input
+                .lines()
+                .enumerate()
+                .filter(|(_, line)| !line.trim().is_empty())
+                .map(|(i, line)| (i + 1, serde_json::from_str(line).map_err(|e| e.to_string())))
+                .collect()
+        };
+
+        for (line, spec) in entries {
+            let job = spec.and_then(JobSpec::into_job).and_then(|job| {
+                self.check_capacity(&job)
+                    .and_then(|()| self.admit(&job))
+                    .map_err(|e| e.to_string())?;
+                Ok(job)
+            });
+            match job {
+                Ok(job) => {
+                    self.insert(job);
+                    report.imported += 1;
+                }
+                Err(e) => report.errors.push((line, e)),
             }
-            None => {
-                self.heap = BinaryHeap::from(all);
-                false
+        }
+        if report.imported > 0 {
+            self.notify_persistence();
+            self.waker.notify_all();
+        }
+        report
+    }
+
+    /// Applies `new_status` to every queued job in `ids` with a single
+    /// persistence write, skipping jobs that can't legally move to it (see
+    /// `update_status`). Returns how many jobs were updated.
+    pub fn update_status_many(&mut self, ids: &[Uuid], new_status: Status) -> usize {
+        let mut updated = 0;
+        for id in ids {
+            if let Some(job) = self.jobs.get_mut(id)
+                && job.status.can_transition_to(&new_status)
+            {
+                job.status = new_status.clone();
+                updated += 1;
             }
         }
+        if updated > 0 {
+            self.notify_persistence();
+        }
+        updated
+    }
+
+    /// Moves every queued job's `execution_time` by `delta_secs`, whatever
+    /// its status, e.g. to push the whole schedule back after a maintenance
+    /// window, with a single persistence write. Relative order is unchanged,
+    /// except that no job lands before now (by the queue's clock): overdue
+    /// jobs that would still be overdue are moved to now instead. A delta
+    /// that would overflow, or move a job that isn't overdue yet into the
+    /// past, is rejected and nothing moves. Returns how many jobs were shifted.
+    pub fn shift_all(&mut self, delta_secs: i64) -> Result<usize, String> {
+        let now = self.clock.now_secs();
+        let mut moved = Vec::with_capacity(self.jobs.len());
+        for job in self.jobs.values() {
+            let Some(time) = job.execution_time.checked_add(delta_secs) else {
+                return Err(format!(
+                    "shifting job {} by {}s is out of range",
+                    job.id, delta_secs
+                ));
+            };
+            if time < now && job.execution_time >= now {
+                return Err(format!(
+                    "shifting by {}s would move job {} into the past",
+                    delta_secs, job.id
+                ));
+            }
+            moved.push((job.id, time.max(now)));
+        }
+        let shifted = moved.len();
+        for (id, time) in moved {
+            if let Some(job) = self.jobs.get_mut(&id) {
+                job.execution_time = time;
+            }
+        }
+        if shifted > 0 {
+            self.heap = self.jobs.values().map(HeapKey::of).collect();
+            self.notify_persistence();
+            self.waker.notify_all();
+        }
+        Ok(shifted)
+    }
+
+    /// Flags queued jobs whose function is not in `known` and handles them
+    /// according to `policy`. Returns the flagged jobs.
+    pub fn check_functions(
+        &mut self,
+        known: &HashSet<String>,
+        policy: UnknownFunctionPolicy,
+    ) -> Vec<Job> {
+        let mut flagged: Vec<Job> = self
+            .jobs
+            .values_mut()
+            .filter(|j| !known.contains(&j.function))
+            .map(|job| {
+                if policy == UnknownFunctionPolicy::Disable {
+                    job.enabled = false;
+                }
+                job.clone()
+            })
+            .collect();
+        if flagged.is_empty() {
+            return flagged;
+        }
+        flagged.sort_by(|a, b| b.cmp(a));
+
+        if policy == UnknownFunctionPolicy::DeadLetter {
+            for job in &flagged {
+                self.jobs.remove(&job.id);
+            }
+            self.prune();
+            self.dead_letters.extend(flagged.iter().cloned());
+        }
+        for job in &flagged {
+            eprintln!(
+                "[Queue] Job {} ('{}') references unknown function '{}'",
+                job.short_id(),
+                job.description,
+                job.function
+            );
+        }
+        if policy != UnknownFunctionPolicy::KeepPending {
+            self.notify_persistence();
+        }
+        flagged
+    }
+
+    /// Jobs moved out of the queue by `UnknownFunctionPolicy::DeadLetter`.
+    pub fn dead_letters(&self) -> &[Job] {
+        &self.dead_letters
     }
 
     pub fn len(&self) -> usize {
-        self.heap.len()
+        self.jobs.len()
     }
 
     pub fn is_empty(&self) -> bool {
-        self.heap.is_empty()
+        self.jobs.is_empty()
     }
 }
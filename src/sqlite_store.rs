@@ -0,0 +1,268 @@
+use crate::job::{Job, Status};
+use crate::persistence::JobStore;
+use rusqlite::{Connection, params};
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+use std::io;
+use std::path::Path;
+use std::sync::Mutex;
+use uuid::Uuid;
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS jobs (
+        id             TEXT PRIMARY KEY,
+        execution_time INTEGER NOT NULL,
+        priority       INTEGER NOT NULL,
+        description    TEXT NOT NULL,
+        function       TEXT NOT NULL,
+        status         TEXT NOT NULL,
+        max_retries    INTEGER NOT NULL DEFAULT 0,
+        retry_count    INTEGER NOT NULL DEFAULT 0,
+        extra          TEXT NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS history (
+        seq            INTEGER PRIMARY KEY AUTOINCREMENT,
+        id             TEXT NOT NULL,
+        status         TEXT NOT NULL,
+        completed_at   INTEGER,
+        job            TEXT NOT NULL
+    );
+";
+
+/// Job fields stored in their own columns; everything else goes into `extra`
+/// as a JSON object so new `Job` fields persist without a schema change.
+const COLUMNS: [&str; 8] = [
+    "id",
+    "execution_time",
+    "priority",
+    "description",
+    "function",
+    "status",
+    "max_retries",
+    "retry_count",
+];
+
+/// Persists jobs in a SQLite `jobs` table keyed by id. `save` only writes rows
+/// that changed since the last load/save and deletes rows for jobs that are gone,
+/// instead of rewriting everything like the JSON store. Finished jobs are
+/// appended to a separate `history` table, each as one JSON document.
+pub struct SqliteStore {
+    conn: Mutex<Connection>,
+    /// Serialized form of each row as last read or written.
+    written: Mutex<HashMap<Uuid, String>>,
+}
+
+impl SqliteStore {
+    /// Opens (or creates) the database at `path` and makes sure the schema exists.
+    pub fn open(path: impl AsRef<Path>) -> rusqlite::Result<Self> {
+        Self::from_connection(Connection::open(path)?)
+    }
+
+    pub fn open_in_memory() -> rusqlite::Result<Self> {
+        Self::from_connection(Connection::open_in_memory()?)
+    }
+
+    fn from_connection(conn: Connection) -> rusqlite::Result<Self> {
+        conn.execute_batch(SCHEMA)?;
+        Self::add_retry_columns(&conn)?;
+        Ok(Self {
+            conn: Mutex::new(conn),
+            written: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// Databases created before the retry columns existed kept those fields
+    /// in `extra`; adds the columns and fills them in from there.
+    fn add_retry_columns(conn: &Connection) -> rusqlite::Result<()> {
+        let exists = conn
+            .prepare("SELECT 1 FROM pragma_table_info('jobs') WHERE name = 'retry_count'")?
+            .exists([])?;
+        if exists {
+            return Ok(());
+        }
+        conn.execute_batch(
+            "BEGIN;
+             ALTER TABLE jobs ADD COLUMN max_retries INTEGER NOT NULL DEFAULT 0;
+             ALTER TABLE jobs ADD COLUMN retry_count INTEGER NOT NULL DEFAULT 0;
+             UPDATE jobs SET
+                 max_retries = COALESCE(json_extract(extra, '$.max_retries'), 0),
+                 retry_count = COALESCE(json_extract(extra, '$.retry_count'), 0);
+             COMMIT;",
+        )
+    }
+
+    /// Number of rows inserted, updated or deleted over this connection's lifetime.
+    pub fn total_changes(&self) -> u64 {
+        self.conn.lock().unwrap().total_changes()
+    }
+
+    fn load_rows(&self) -> rusqlite::Result<Vec<Job>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT id, execution_time, priority, description, function, status,
+                    max_retries, retry_count, extra
+             FROM jobs",
+        )?;
+        let rows = stmt.query_map([], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, i64>(1)?,
+                row.get::<_, u8>(2)?,
+                row.get::<_, String>(3)?,
+                row.get::<_, String>(4)?,
+                row.get::<_, String>(5)?,
+                row.get::<_, u32>(6)?,
+                row.get::<_, u32>(7)?,
+                row.get::<_, String>(8)?,
+            ))
+        })?;
+
+        let mut jobs = Vec::new();
+        for row in rows {
+            let (
+                id,
+                execution_time,
+                priority,
+                description,
+                function,
+                status,
+                max_retries,
+                retry_count,
+                extra,
+            ) = row?;
+            let job = (|| -> Result<Job, String> {
+                let mut fields: Map<String, Value> =
+                    serde_json::from_str(&extra).map_err(|e| e.to_string())?;
+                let status: Status = status.parse()?;
+                fields.insert("id".into(), id.clone().into());
+                fields.insert("execution_time".into(), execution_time.into());
+                fields.insert("priority".into(), priority.into());
+                fields.insert("description".into(), description.into());
+                fields.insert("function".into(), function.into());
+                fields.insert("status".into(), status.as_str().into());
+                fields.insert("max_retries".into(), max_retries.into());
+                fields.insert("retry_count".into(), retry_count.into());
+                serde_json::from_value(Value::Object(fields)).map_err(|e| e.to_string())
+            })();
+            match job {
+                Ok(job) => jobs.push(job),
+                Err(e) => eprintln!("[Persistence] Skipping unreadable job row {}: {}", id, e),
+            }
+        }
+        Ok(jobs)
+    }
+
+    fn save_rows(&self, jobs: &[Job]) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let mut written = self.written.lock().unwrap();
+        let mut next_written = HashMap::with_capacity(jobs.len());
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        {
+            let mut upsert = tx.prepare(
+                "INSERT INTO jobs (id, execution_time, priority, description, function, status,
+                                   max_retries, retry_count, extra)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)
+                 ON CONFLICT(id) DO UPDATE SET
+                     execution_time = excluded.execution_time,
+                     priority = excluded.priority,
+                     description = excluded.description,
+                     function = excluded.function,
+                     status = excluded.status,
+                     max_retries = excluded.max_retries,
+                     retry_count = excluded.retry_count,
+                     extra = excluded.extra",
+            )?;
+            for job in jobs {
+                let serialized = serde_json::to_string(job)?;
+                if written.get(&job.id) != Some(&serialized) {
+                    let Value::Object(mut extra) = serde_json::to_value(job)? else {
+                        unreachable!("Job always serializes to an object");
+                    };
+                    for column in COLUMNS {
+                        extra.remove(column);
+                    }
+                    upsert.execute(params![
+                        job.id.to_string(),
+                        job.execution_time,
+                        job.priority,
+                        job.description,
+                        job.function,
+                        job.status.as_str(),
+                        job.max_retries,
+                        job.retry_count,
+                        Value::Object(extra).to_string(),
+                    ])?;
+                }
+                next_written.insert(job.id, serialized);
+            }
+
+            let mut delete = tx.prepare("DELETE FROM jobs WHERE id = ?1")?;
+            for id in written.keys().filter(|id| !next_written.contains_key(id)) {
+                delete.execute(params![id.to_string()])?;
+            }
+        }
+        tx.commit()?;
+        *written = next_written;
+        Ok(())
+    }
+
+    fn insert_history(&self, job: &Job) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        self.conn.lock().unwrap().execute(
+            "INSERT INTO history (id, status, completed_at, job) VALUES (?1, ?2, ?3, ?4)",
+            params![
+                job.id.to_string(),
+                job.status.as_str(),
+                job.completed_at,
+                serde_json::to_string(job)?,
+            ],
+        )?;
+        Ok(())
+    }
+
+    fn history_rows(&self, limit: usize) -> rusqlite::Result<Vec<String>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare("SELECT job FROM history ORDER BY seq DESC LIMIT ?1")?;
+        let limit = i64::try_from(limit).unwrap_or(i64::MAX);
+        let mut rows = stmt
+            .query_map(params![limit], |row| row.get::<_, String>(0))?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        rows.reverse();
+        Ok(rows)
+    }
+}
+
+impl JobStore for SqliteStore {
+    fn load(&self) -> io::Result<Vec<Job>> {
+        let jobs = self.load_rows().map_err(io::Error::other)?;
+        let mut written = self.written.lock().unwrap();
+        *written = jobs
+            .iter()
+            .filter_map(|j| Some((j.id, serde_json::to_string(j).ok()?)))
+            .collect();
+        Ok(jobs)
+    }
+
+    fn save(&self, jobs: &[Job]) -> io::Result<()> {
+        self.save_rows(jobs).map_err(io::Error::other)
+    }
+
+    fn append_history(&self, job: &Job) -> io::Result<()> {
+        self.insert_history(job).map_err(io::Error::other)
+    }
+
+    fn load_history(&self, limit: usize) -> Vec<Job> {
+        let rows = self.history_rows(limit).unwrap_or_else(|e| {
+            eprintln!("[Persistence] Failed to load history from SQLite: {}", e);
+            Vec::new()
+        });
+        rows.iter()
+            .filter_map(|row| match serde_json::from_str(row) {
+                Ok(job) => Some(job),
+                Err(e) => {
+                    eprintln!("[Persistence] Skipping unreadable history row: {}", e);
+                    None
+                }
+            })
+            .collect()
+    }
+}
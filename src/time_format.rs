@@ -0,0 +1,144 @@
+use chrono::{DateTime, SecondsFormat};
+use serde::Serializer;
+use serde::de::{self, Deserializer, Visitor};
+use std::fmt;
+use std::time::Duration;
+
+/// Spelling used for time fields when jobs are written out.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TimeFormat {
+    /// Unix seconds, e.g. `1767225600`.
+    #[default]
+    Unix,
+    /// RFC3339 in UTC, e.g. `"2026-01-01T00:00:00Z"`; easier to read and hand-edit.
+    Rfc3339,
+}
+
+/// Parses an RFC3339 datetime into Unix seconds.
+pub fn parse_rfc3339(s: &str) -> Result<i64, String> {
+    DateTime::parse_from_rfc3339(s)
+        .map(|t| t.timestamp())
+        .map_err(|e| format!("invalid RFC3339 time '{}': {}", s, e))
+}
+
+/// Formats Unix seconds as an RFC3339 datetime in UTC.
+pub fn format_rfc3339(secs: i64) -> String {
+    DateTime::from_timestamp(secs, 0)
+        .map(|t| t.to_rfc3339_opts(SecondsFormat::Secs, true))
+        .unwrap_or_else(|| secs.to_string())
+}
+
+/// Parses a human-friendly delay such as `"45s"`, `"2d"` or `"1h30m"`: one
+/// or more whole numbers, each followed by a unit (`d`, `h`, `m` or `s`).
+/// Zero and negative delays are rejected, since they would be in the past.
+pub fn parse_duration(s: &str) -> Result<Duration, String> {
+    let invalid = |reason: &str| format!("invalid duration '{}': {}", s, reason);
+    let mut secs: u64 = 0;
+    let mut digits = String::new();
+    for c in s.trim().chars() {
+        if c.is_ascii_digit() {
+            digits.push(c);
+            continue;
+        }
+        let unit = match c {
+            'd' => 86_400,
+            'h' => 3_600,
+            'm' => 60,
+            's' => 1,
+            '-' => return Err(invalid("negative delays are in the past")),
+            _ => return Err(invalid(&format!("unknown unit '{}'", c))),
+        };
+        if digits.is_empty() {
+            return Err(invalid(&format!("'{}' has no number", c)));
+        }
+        let value: u64 = digits.parse().map_err(|_| invalid("number is too large"))?;
+        secs = value
+            .checked_mul(unit)
+            .and_then(|v| secs.checked_add(v))
+            .ok_or_else(|| invalid("too large"))?;
+        digits.clear();
+    }
+    if !digits.is_empty() {
+        return Err(invalid(&format!("'{}' has no unit", digits)));
+    }
+    if secs == 0 {
+        return Err(invalid("delay must be in the future"));
+    }
+    Ok(Duration::from_secs(secs))
+}
+
+/// Serializes Unix seconds as an integer and deserializes either an integer
+/// or an RFC3339 string, for use with `#[serde(with = "...")]`.
+pub mod unix_or_rfc3339 {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(secs: &i64, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_i64(*secs)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<i64, D::Error> {
+        deserializer.deserialize_any(TimeVisitor)
+    }
+}
+
+/// Like `unix_or_rfc3339`, for optional times such as `completed_at`.
+pub mod unix_or_rfc3339_opt {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(secs: &Option<i64>, serializer: S) -> Result<S::Ok, S::Error> {
+        match secs {
+            Some(secs) => serializer.serialize_some(secs),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<i64>, D::Error> {
+        deserializer.deserialize_option(OptionalTimeVisitor)
+    }
+}
+
+struct OptionalTimeVisitor;
+
+impl<'de> Visitor<'de> for OptionalTimeVisitor {
+    type Value = Option<i64>;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("null, Unix seconds or an RFC3339 datetime string")
+    }
+
+    fn visit_none<E: de::Error>(self) -> Result<Option<i64>, E> {
+        Ok(None)
+    }
+
+    fn visit_unit<E: de::Error>(self) -> Result<Option<i64>, E> {
+        Ok(None)
+    }
+
+    fn visit_some<D: Deserializer<'de>>(self, deserializer: D) -> Result<Option<i64>, D::Error> {
+        deserializer.deserialize_any(TimeVisitor).map(Some)
+    }
+}
+
+struct TimeVisitor;
+
+impl Visitor<'_> for TimeVisitor {
+    type Value = i64;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("Unix seconds or an RFC3339 datetime string")
+    }
+
+    fn visit_i64<E: de::Error>(self, v: i64) -> Result<i64, E> {
+        Ok(v)
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<i64, E> {
+        i64::try_from(v).map_err(|_| E::custom(format!("time {} is out of range", v)))
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<i64, E> {
+        parse_rfc3339(v).map_err(E::custom)
+    }
+}
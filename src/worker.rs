@@ -1,56 +1,541 @@
 use std::collections::HashMap;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Condvar, Mutex, PoisonError};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+use uuid::Uuid;
 
-use crate::job::Job;
+use crate::engine::CompletionSender;
+use crate::job::{Isolation, Job, JobSpec, SpecError, Status};
+use crate::logging::{DefaultFormatter, LogEvent, LogFormatter};
+#[cfg(feature = "metrics")]
+use crate::metrics::Metrics;
+use crate::queue::QueueManager;
 
-/// Type alias for a function pointer that takes no arguments and returns nothing
-type JobFn = fn();
+/// Type alias for a job function: it receives the job (including its payload)
+/// and a channel for human-readable log lines, and reports failure with `Err`
+pub type JobFn = fn(&Job, Sender<String>) -> Result<(), String>;
 
+/// Result of executing a job function
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Outcome {
+    Success,
+    /// The function returned an error; holds its message
+    Failed(String),
+    /// The function panicked; holds the panic message
+    Panicked(String),
+    /// The function was still running when the job's timeout (in seconds) ran out
+    TimedOut(u64),
+    /// No function is registered under the requested name
+    NotFound,
+    /// The job was cancelled with `Worker::cancel` while it ran
+    Cancelled,
+}
+
+/// What happened to a job on the worker, for observers that need more than
+/// log lines
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum JobEvent {
+    Started(Uuid),
+    Succeeded(Uuid),
+    /// A failed run, whether or not it will be retried
+    Failed {
+        id: Uuid,
+        error: String,
+    },
+    /// The job went back onto the queue; `attempt` counts retries from 1
+    Retrying {
+        id: Uuid,
+        attempt: u32,
+    },
+    /// The job was stopped by `Worker::cancel`; it is not retried
+    Cancelled(Uuid),
+}
+
+/// Cloning a worker is cheap; clones share the same registry.
+#[derive(Clone)]
 pub struct Worker {
-    registry: HashMap<String, JobFn>,
+    registry: Arc<Mutex<HashMap<String, JobFn>>>,
+    log_tx: Sender<String>,
+    /// False until `with_log_sink`; by default log lines are dropped
+    has_log_sink: bool,
+    threads: usize,
+    /// Where failed jobs with retries left are pushed back to
+    queue: Option<Arc<Mutex<QueueManager>>>,
+    formatter: Arc<dyn LogFormatter>,
+    /// Structured counterpart of the log lines, for monitoring
+    events: Option<Sender<JobEvent>>,
+    /// Told about every finished job, for engines limiting jobs in flight
+    completions: Option<CompletionSender>,
+    /// Number of jobs currently running, shared by all clones
+    in_flight: Arc<(Mutex<usize>, Condvar)>,
+    /// Cancel flags of the jobs currently running, by id
+    running: Arc<Mutex<HashMap<Uuid, Arc<AtomicBool>>>>,
+    #[cfg(feature = "metrics")]
+    metrics: Option<Arc<Metrics>>,
+}
+
+/// Counts a job as in flight until dropped
+struct InFlight(Arc<(Mutex<usize>, Condvar)>);
+
+impl InFlight {
+    fn new(counter: &Arc<(Mutex<usize>, Condvar)>) -> Self {
+        *counter.0.lock().unwrap_or_else(PoisonError::into_inner) += 1;
+        Self(Arc::clone(counter))
+    }
+}
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        let (count, idle) = &*self.0;
+        *count.lock().unwrap_or_else(PoisonError::into_inner) -= 1;
+        idle.notify_all();
+    }
 }
 
 impl Worker {
-    /// Initialize a new worker with an empty registry
+    /// Initialize a new worker with an empty registry. Log lines from job
+    /// functions are discarded until a sink is set with `with_log_sink`.
     pub fn new() -> Self {
+        let (log_tx, _) = mpsc::channel();
         Self {
-            registry: HashMap::new(),
+            registry: Arc::new(Mutex::new(HashMap::new())),
+            log_tx,
+            has_log_sink: false,
+            threads: 1,
+            queue: None,
+            formatter: Arc::new(DefaultFormatter),
+            events: None,
+            completions: None,
+            in_flight: Arc::new((Mutex::new(0), Condvar::new())),
+            running: Arc::new(Mutex::new(HashMap::new())),
+            #[cfg(feature = "metrics")]
+            metrics: None,
         }
     }
 
-    /// Register a function string to a concrete function pointer
-    pub fn register(&mut self, name: &str, f: JobFn) {
-        self.registry.insert(name.to_string(), f);
+    /// Number of threads `start` runs jobs on concurrently (at least 1)
+    pub fn with_threads(mut self, threads: usize) -> Self {
+        self.threads = threads.max(1);
+        self
     }
 
-    /// The execution engine: looks up the string in the map and calls the function
-    pub fn run_job(&self, job: &Job) {
-        if let Some(func) = self.registry.get(&job.function) {
-            println!("[Worker] Executing: {}", job.function);
-            func(); // Execute the function pointer
-        } else {
-            eprintln!(
-                "[Worker] Error: No function registered for '{}'",
-                job.function
-            );
+    pub fn threads(&self) -> usize {
+        self.threads
+    }
+
+    pub fn has_log_sink(&self) -> bool {
+        self.has_log_sink
+    }
+
+    /// Sends the log lines written by job functions to `log_tx`
+    pub fn with_log_sink(mut self, log_tx: Sender<String>) -> Self {
+        self.log_tx = log_tx;
+        self.has_log_sink = true;
+        self
+    }
+
+    /// Checks that the sink given to `with_log_sink` is still being read by
+    /// sending it a line. Workers without a sink pass trivially.
+    pub fn check_log_sink(&self) -> Result<(), String> {
+        if self.has_log_sink && self.log_tx.send("[Worker] Self-test".to_string()).is_err() {
+            return Err("worker log sink is disconnected".to_string());
         }
+        Ok(())
     }
 
-    /// Starts a simple blocking loop to process jobs from the channel
-    pub fn start(&self, rx: std::sync::mpsc::Receiver<Job>) {
-        for job in rx {
-            self.run_job(&job);
+    /// Re-enqueues failed jobs that still have retries left onto `queue`.
+    /// Without a queue, a failed job is only marked `Failed`.
+    pub fn with_queue(mut self, queue: Arc<Mutex<QueueManager>>) -> Self {
+        self.queue = Some(queue);
+        self
+    }
+
+    /// Records each run's outcome and duration in `metrics`.
+    #[cfg(feature = "metrics")]
+    pub fn with_metrics(mut self, metrics: Arc<Metrics>) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    /// Sends a `JobEvent` for every start, success, failure and retry, in
+    /// addition to the log lines
+    pub fn with_event_sink(mut self, events: Sender<JobEvent>) -> Self {
+        self.events = Some(events);
+        self
+    }
+
+    /// Reports each job back to the engine once it has run, whether it
+    /// succeeded, failed or was re-enqueued for a retry
+    pub fn with_completions(mut self, completions: CompletionSender) -> Self {
+        self.completions = Some(completions);
+        self
+    }
+
+    /// Renders the worker's own log lines with `formatter` instead of the
+    /// default `[Worker]` format
+    pub fn with_log_formatter(mut self, formatter: impl LogFormatter + 'static) -> Self {
+        self.formatter = Arc::new(formatter);
+        self
+    }
+
+    /// Register a function string to a concrete function pointer. Fails if
+    /// the name is already taken; use `register_or_replace` to override it.
+    pub fn register(&mut self, name: &str, f: JobFn) -> Result<(), String> {
+        let mut registry = self.registry.lock().unwrap();
+        if registry.contains_key(name) {
+            return Err(format!("function '{}' is already registered", name));
         }
+        registry.insert(name.to_string(), f);
+        Ok(())
+    }
+
+    /// Register a function, replacing any function already registered under
+    /// `name`
+    pub fn register_or_replace(&mut self, name: &str, f: JobFn) {
+        self.registry.lock().unwrap().insert(name.to_string(), f);
+    }
+
+    /// Names of all registered functions, sorted
+    pub fn registered_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.registry.lock().unwrap().keys().cloned().collect();
+        names.sort();
+        names
+    }
+
+    fn lookup(&self, name: &str) -> Option<JobFn> {
+        self.registry.lock().unwrap().get(name).copied()
+    }
+
+    /// Validates a job spec as if it were about to be created, including
+    /// that its function is registered on this worker
+    pub fn validate_spec(&self, spec: &JobSpec) -> Result<(), SpecError> {
+        spec.validate_with(|function| self.lookup(function).is_some())
+    }
+
+    /// The execution engine: looks up the string in the map and calls the
+    /// function, then records the result on the job. A failed, panicking or
+    /// timed-out job uses up a retry and goes back onto the queue if it has
+    /// any left and the queue accepts it; otherwise its final status is
+    /// reported to the queue.
+    pub fn run_job(&self, job: &mut Job) {
+        let _in_flight = InFlight::new(&self.in_flight);
+        self.running
+            .lock()
+            .unwrap()
+            .insert(job.id, Arc::clone(&job.cancel));
+        self.run_and_record(job);
+        self.running.lock().unwrap().remove(&job.id);
+        if let Some(completions) = &self.completions {
+            completions.send(job.id);
+        }
+    }
+
+    /// Asks the running job `id` to stop by setting its cancel flag. Job
+    /// functions notice it through `Job::is_cancelled`; once the function
+    /// returns the job is marked `Cancelled` and not retried. Returns false
+    /// if no job with that id is running on this worker.
+    pub fn cancel(&self, id: Uuid) -> bool {
+        match self.running.lock().unwrap().get(&id) {
+            Some(flag) => {
+                flag.store(true, Ordering::SeqCst);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Runs the job and records the outcome on it, re-enqueueing it if a
+    /// retry is left
+    fn run_and_record(&self, job: &mut Job) {
+        self.emit(JobEvent::Started(job.id));
+        #[cfg(feature = "metrics")]
+        let started = std::time::Instant::now();
+        let outcome = self.execute(job);
+        #[cfg(feature = "metrics")]
+        if let Some(metrics) = &self.metrics {
+            metrics.observe_duration(started.elapsed());
+            match outcome {
+                Outcome::Success => metrics.record_succeeded(),
+                Outcome::Cancelled => {}
+                _ => metrics.record_failed(),
+            }
+        }
+        let (retryable, error) = match outcome {
+            Outcome::Success => {
+                job.status = Status::Success;
+                self.emit(JobEvent::Succeeded(job.id));
+                (false, None)
+            }
+            Outcome::Failed(error) => {
+                eprintln!(
+                    "{}",
+                    self.formatter
+                        .format(&LogEvent::Failed { job, error: &error })
+                );
+                (true, Some(error))
+            }
+            Outcome::Panicked(message) => {
+                eprintln!(
+                    "{}",
+                    self.formatter.format(&LogEvent::Panicked {
+                        job,
+                        message: &message
+                    })
+                );
+                (true, Some(format!("panicked: {}", message)))
+            }
+            Outcome::TimedOut(secs) => {
+                eprintln!(
+                    "{}",
+                    self.formatter.format(&LogEvent::TimedOut { job, secs })
+                );
+                (true, Some(format!("timed out after {}s", secs)))
+            }
+            Outcome::NotFound => {
+                eprintln!(
+                    "[Worker] Error: No function registered for '{}'",
+                    job.function
+                );
+                job.status = Status::Failed;
+                let error = format!("no function registered for '{}'", job.function);
+                (false, Some(error))
+            }
+            Outcome::Cancelled => {
+                println!("[Worker] Job {} cancelled", job.id);
+                job.status = Status::Cancelled;
+                self.emit(JobEvent::Cancelled(job.id));
+                (false, None)
+            }
+        };
+        if let Some(error) = error {
+            self.emit(JobEvent::Failed { id: job.id, error });
+        }
+
+        if retryable && job.fail_and_retry() {
+            match &self.queue {
+                Some(queue) => {
+                    if queue.lock().unwrap().push(job.clone()) {
+                        println!("{}", self.formatter.format(&LogEvent::Retrying(job)));
+                        self.emit(JobEvent::Retrying {
+                            id: job.id,
+                            attempt: job.retry_count,
+                        });
+                        return;
+                    }
+                    // The queue logged why it refused the retry; this run
+                    // was the job's last
+                    job.status = Status::Failed;
+                }
+                None => job.status = Status::Failed,
+            }
+        }
+        job.completed_at = Some(chrono::Utc::now().timestamp());
+        // Lets jobs that depend on this one go ahead once it has succeeded
+        if let Some(queue) = &self.queue {
+            let mut queue = queue.lock().unwrap();
+            queue.record_status(job.id, job.status.clone());
+            queue.append_history(job);
+        }
+    }
+
+    fn emit(&self, event: JobEvent) {
+        if let Some(events) = &self.events {
+            let _ = events.send(event);
+        }
+    }
+
+    /// Runs a registered function synchronously, without any queue or engine,
+    /// and reports how it went. With `timeout_secs` it is given up on like a
+    /// job with that timeout, returning `Outcome::TimedOut`.
+    pub fn run_now(
+        &self,
+        function: &str,
+        payload: &serde_json::Value,
+        timeout_secs: Option<u64>,
+    ) -> Outcome {
+        let mut job = Job::immediate(function, payload.clone());
+        job.timeout_secs = timeout_secs;
+        self.execute(&job)
+    }
+
+    /// Looks up and calls a job's function, isolating the caller from panics.
+    ///
+    /// A job with `timeout_secs` runs on a separate thread that is abandoned
+    /// once the timeout passes. Threads can't be killed, so a function that
+    /// never returns leaks its thread, but the worker itself moves on.
+    fn execute(&self, job: &Job) -> Outcome {
+        let Some(func) = self.lookup(&job.function) else {
+            return Outcome::NotFound;
+        };
+        println!("{}", self.formatter.format(&LogEvent::Executing(job)));
+        let log_tx = self.log_tx.clone();
+        let outcome = match job.timeout_secs {
+            None => call(func, job, log_tx),
+            Some(secs) => {
+                let (done_tx, done_rx) = mpsc::channel();
+                let detached = job.clone();
+                thread::spawn(move || {
+                    let _ = done_tx.send(call(func, &detached, log_tx));
+                });
+                done_rx
+                    .recv_timeout(Duration::from_secs(secs))
+                    .unwrap_or(Outcome::TimedOut(secs))
+            }
+        };
+        // However the function gave up, a cancelled run is reported as such
+        if job.is_cancelled() {
+            return Outcome::Cancelled;
+        }
+        outcome
+    }
+
+    /// Runs a job on its own OS thread so the worker loop can keep going
+    pub fn run_dedicated(&self, mut job: Job) {
+        println!("[Worker] Executing on dedicated thread: {}", job.function);
+        let worker = self.clone();
+        let in_flight = InFlight::new(&self.in_flight);
+        thread::spawn(move || {
+            worker.run_job(&mut job);
+            drop(in_flight);
+        });
+    }
+
+    /// Runs a job according to its isolation setting
+    pub fn handle(&self, mut job: Job) {
+        match job.isolation {
+            Isolation::Pooled => self.run_job(&mut job),
+            Isolation::Dedicated => self.run_dedicated(job),
+        }
+    }
+
+    /// Blocks until no job is running on this worker or its clones,
+    /// including jobs on dedicated threads. Call it after closing the job
+    /// channel so no new jobs start in the meantime.
+    pub fn shutdown(&self) {
+        let (count, idle) = &*self.in_flight;
+        let mut count = count.lock().unwrap_or_else(PoisonError::into_inner);
+        while *count > 0 {
+            count = idle.wait(count).unwrap_or_else(PoisonError::into_inner);
+        }
+    }
+
+    /// Processes jobs from the channel until it closes (every sender is
+    /// dropped), finishing the jobs already taken before returning. With more
+    /// than one thread configured, jobs are pulled and run concurrently; this
+    /// call blocks until every thread has finished.
+    pub fn start(&self, rx: Receiver<Job>) {
+        if self.threads == 1 {
+            for job in rx {
+                self.handle(job);
+            }
+            return;
+        }
+
+        let rx = Arc::new(Mutex::new(rx));
+        let pool: Vec<JoinHandle<()>> = (0..self.threads)
+            .map(|_| {
+                let worker = self.clone();
+                let rx = Arc::clone(&rx);
+                thread::spawn(move || {
+                    loop {
+                        // Hold the lock only while receiving, not while running the job
+                        let next = rx.lock().unwrap_or_else(PoisonError::into_inner).recv();
+                        match next {
+                            Ok(job) => worker.handle(job),
+                            Err(_) => break,
+                        }
+                    }
+                })
+            })
+            .collect();
+        for handle in pool {
+            let _ = handle.join();
+        }
+    }
+
+    /// Like `start`, but on a supervised background thread that is respawned
+    /// with the same registry if it ever dies
+    pub fn start_supervised(self, rx: Receiver<Job>) -> JoinHandle<()> {
+        let worker = Arc::new(self);
+        supervise(rx, move |job| worker.handle(job))
+    }
+}
+
+/// Runs `handler` for every job received on `rx` in a worker thread, and
+/// respawns that thread if it panics. The returned supervisor thread exits
+/// once the channel is closed.
+pub fn supervise<F>(rx: Receiver<Job>, handler: F) -> JoinHandle<()>
+where
+    F: Fn(Job) + Send + Sync + 'static,
+{
+    let rx = Arc::new(Mutex::new(rx));
+    let handler = Arc::new(handler);
+    thread::spawn(move || {
+        loop {
+            let rx = Arc::clone(&rx);
+            let handler = Arc::clone(&handler);
+            let worker = thread::spawn(move || {
+                loop {
+                    // A panicking handler never holds the lock, so poisoning is harmless
+                    let next = rx.lock().unwrap_or_else(PoisonError::into_inner).recv();
+                    match next {
+                        Ok(job) => handler(job),
+                        Err(_) => break,
+                    }
+                }
+            });
+            match worker.join() {
+                Ok(()) => break,
+                Err(_) => eprintln!("[Supervisor] Worker thread died. Restarting..."),
+            }
+        }
+    })
+}
+
+fn call(func: JobFn, job: &Job, log_tx: Sender<String>) -> Outcome {
+    match panic::catch_unwind(AssertUnwindSafe(|| func(job, log_tx))) {
+        Ok(Ok(())) => Outcome::Success,
+        Ok(Err(msg)) => Outcome::Failed(msg),
+        Err(payload) => Outcome::Panicked(panic_message(payload.as_ref())),
+    }
+}
+
+fn panic_message(payload: &(dyn std::any::Any + Send)) -> String {
+    if let Some(msg) = payload.downcast_ref::<&str>() {
+        msg.to_string()
+    } else if let Some(msg) = payload.downcast_ref::<String>() {
+        msg.clone()
+    } else {
+        "unknown panic".to_string()
+    }
+}
+
+impl Default for Worker {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
 // --- Task Functions ---
 
-pub fn send_email() {
-    println!("📧 [Task] Sending email...");
+pub fn send_email(job: &Job, log_tx: Sender<String>) -> Result<(), String> {
+    let to = job.payload["to"].as_str().unwrap_or("<no recipient>");
+    let _ = log_tx.send(format!("📧 [Task] Sending email to {}...", to));
     // Logic for sending email here
+    Ok(())
 }
 
-pub fn backup_db() {
-    println!("🗄️ [Task] Backing up database...");
+pub fn backup_db(_job: &Job, log_tx: Sender<String>) -> Result<(), String> {
+    let _ = log_tx.send("🗄️ [Task] Backing up database...".to_string());
     // Logic for DB backup here
+    Ok(())
+}
+
+pub fn apply_hotfix(_job: &Job, log_tx: Sender<String>) -> Result<(), String> {
+    let _ = log_tx.send("🩹 [Task] Applying hotfix...".to_string());
+    // Logic for applying the hotfix here
+    Ok(())
 }
@@ -0,0 +1,250 @@
+#![cfg(feature = "api")]
+
+use chrono::Utc;
+use scheduler::api::{ApiOptions, ApiServer, OutOfRange};
+use scheduler::job::MAX_PAYLOAD_BYTES;
+use scheduler::queue::QueueManager;
+use serde_json::{Value, json};
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpStream};
+use std::sync::{Arc, Mutex};
+
+/// Sends one request and returns the status code and body.
+fn request(addr: SocketAddr, method: &str, path: &str, body: &str) -> (u16, String) {
+    let mut stream = TcpStream::connect(addr).unwrap();
+    write!(
+        stream,
+        "{method} {path} HTTP/1.1\r\nHost: {addr}\r\nConnection: close\r\n\
+         Content-Type: application/json\r\nContent-Length: {}\r\n\r\n{body}",
+        body.len()
+    )
+    .unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    let status = response[9..12].parse().unwrap();
+    let body = response
+        .split_once("\r\n\r\n")
+        .map(|(_, body)| body.to_string())
+        .unwrap_or_default();
+    (status, body)
+}
+
+#[test]
+fn submitted_job_is_listed_and_deletable() {
+    let queue = Arc::new(Mutex::new(QueueManager::new()));
+    let server = ApiServer::start("127.0.0.1:0", Arc::clone(&queue)).unwrap();
+    let addr = server.local_addr();
+
+    let spec = json!({
+        "execution_time": Utc::now().timestamp() + 60,
+        "priority": 3,
+        "description": "Nightly report",
+        "function": "email_fn",
+        "max_retries": 2,
+    });
+    let (status, body) = request(addr, "POST", "/jobs", &spec.to_string());
+    assert_eq!(status, 201, "{body}");
+    let id = serde_json::from_str::<Value>(&body).unwrap()["id"].clone();
+
+    let (status, body) = request(addr, "GET", "/jobs", "");
+    assert_eq!(status, 200);
+    let jobs: Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(jobs[0]["id"], id);
+    assert_eq!(jobs[0]["description"], "Nightly report");
+    assert_eq!(jobs[0]["max_retries"], 2);
+
+    let path = format!("/jobs/{}", id.as_str().unwrap());
+    assert_eq!(request(addr, "DELETE", &path, "").0, 204);
+    assert_eq!(request(addr, "DELETE", &path, "").0, 404);
+    assert!(queue.lock().unwrap().is_empty());
+    server.stop();
+}
+
+#[test]
+fn past_dated_job_is_rejected() {
+    let queue = Arc::new(Mutex::new(QueueManager::new()));
+    let server = ApiServer::start("127.0.0.1:0", Arc::clone(&queue)).unwrap();
+
+    let spec = json!({
+        "execution_time": Utc::now().timestamp() - 60,
+        "priority": 1,
+        "description": "Too late",
+        "function": "email_fn",
+    });
+    let (status, body) = request(server.local_addr(), "POST", "/jobs", &spec.to_string());
+    assert_eq!(status, 400);
+    assert!(body.contains("in the past"), "{body}");
+    assert!(queue.lock().unwrap().is_empty());
+}
+
+#[test]
+fn full_queue_rejects_submissions() {
+    let queue = Arc::new(Mutex::new(QueueManager::with_capacity(0)));
+    let server = ApiServer::start("127.0.0.1:0", Arc::clone(&queue)).unwrap();
+
+    let spec = json!({
+        "execution_time": Utc::now().timestamp() + 60,
+        "priority": 1,
+        "description": "No room",
+        "function": "email_fn",
+    });
+    let (status, body) = request(server.local_addr(), "POST", "/jobs", &spec.to_string());
+    assert_eq!(status, 503);
+    assert!(body.contains("queue is full"), "{body}");
+}
+
+#[test]
+fn duplicate_idempotency_key_is_a_conflict() {
+    let queue = Arc::new(Mutex::new(QueueManager::new()));
+    let server = ApiServer::start("127.0.0.1:0", Arc::clone(&queue)).unwrap();
+
+    let spec = json!({
+        "execution_time": Utc::now().timestamp() + 60,
+        "priority": 1,
+        "description": "Charge card",
+        "function": "billing_fn",
+        "idempotency_key": "order-42",
+    });
+    let (status, body) = request(server.local_addr(), "POST", "/jobs", &spec.to_string());
+    assert_eq!(status, 201, "{body}");
+    let (status, body) = request(server.local_addr(), "POST", "/jobs", &spec.to_string());
+    assert_eq!(status, 409);
+    assert!(body.contains("order-42"), "{body}");
+    assert_eq!(queue.lock().unwrap().len(), 1);
+}
+
+#[test]
+fn validate_checks_specs_without_queueing_them() {
+    use std::collections::HashSet;
+
+    let queue = Arc::new(Mutex::new(QueueManager::new()));
+    let known = HashSet::from(["email_fn".to_string()]);
+    queue
+        .lock()
+        .unwrap()
+        .set_known_functions(Some(Arc::new(known)));
+    let server = ApiServer::start("127.0.0.1:0", Arc::clone(&queue)).unwrap();
+    let addr = server.local_addr();
+    let spec = |function: &str, payload: Value| {
+        json!({
+            "execution_time": Utc::now().timestamp() + 60,
+            "priority": 1,
+            "description": "Welcome email",
+            "function": function,
+            "payload": payload,
+        })
+        .to_string()
+    };
+
+    let (status, body) = request(addr, "POST", "/jobs/validate", &spec("email_fn", json!({})));
+    assert_eq!(status, 200, "{body}");
+    assert_eq!(serde_json::from_str::<Value>(&body).unwrap()["valid"], true);
+
+    let (status, body) = request(addr, "POST", "/jobs/validate", r#"{"priority": "#);
+    assert_eq!(status, 400);
+    assert!(serde_json::from_str::<Value>(&body).unwrap()["error"].is_string());
+
+    let oversized = json!("x".repeat(MAX_PAYLOAD_BYTES));
+    let (status, body) = request(addr, "POST", "/jobs/validate", &spec("email_fn", oversized));
+    assert_eq!(status, 400);
+    let details = &serde_json::from_str::<Value>(&body).unwrap()["details"];
+    assert_eq!(details["kind"], "payload_too_large");
+    assert_eq!(details["detail"]["len"], MAX_PAYLOAD_BYTES + 2);
+    assert_eq!(details["detail"]["max"], MAX_PAYLOAD_BYTES);
+
+    let (status, body) = request(addr, "POST", "/jobs/validate", &spec("sms_fn", json!({})));
+    assert_eq!(status, 400);
+    let details = &serde_json::from_str::<Value>(&body).unwrap()["details"];
+    assert_eq!(
+        *details,
+        json!({ "kind": "unknown_function", "detail": "sms_fn" })
+    );
+
+    assert!(queue.lock().unwrap().is_empty());
+}
+
+#[test]
+fn execution_time_accepts_unix_seconds_or_rfc3339() {
+    use scheduler::time_format::format_rfc3339;
+
+    let queue = Arc::new(Mutex::new(QueueManager::new()));
+    let server = ApiServer::start("127.0.0.1:0", Arc::clone(&queue)).unwrap();
+    let addr = server.local_addr();
+    let at = Utc::now().timestamp() + 3600;
+    let spec = |execution_time: Value| {
+        json!({
+            "execution_time": execution_time,
+            "priority": 1,
+            "description": "Timestamped",
+            "function": "email_fn",
+        })
+        .to_string()
+    };
+
+    let (status, body) = request(addr, "POST", "/jobs", &spec(json!(at)));
+    assert_eq!(status, 201, "{body}");
+    let (status, body) = request(addr, "POST", "/jobs", &spec(json!(format_rfc3339(at + 1))));
+    assert_eq!(status, 201, "{body}");
+    let times: Vec<i64> = queue
+        .lock()
+        .unwrap()
+        .snapshot()
+        .iter()
+        .map(|job| job.execution_time)
+        .collect();
+    assert_eq!(times, [at, at + 1]);
+
+    let (status, body) = request(addr, "POST", "/jobs", &spec(json!("next tuesday")));
+    assert_eq!(status, 400);
+    assert!(body.contains("next tuesday"), "{body}");
+    assert_eq!(queue.lock().unwrap().len(), 2);
+}
+
+#[test]
+fn out_of_range_values_are_rejected_by_default() {
+    let queue = Arc::new(Mutex::new(QueueManager::new()));
+    let server = ApiServer::start("127.0.0.1:0", Arc::clone(&queue)).unwrap();
+
+    let spec = json!({
+        "execution_time": Utc::now().timestamp() + 60,
+        "priority": 300,
+        "description": "Too important",
+        "function": "email_fn",
+    });
+    let (status, body) = request(server.local_addr(), "POST", "/jobs", &spec.to_string());
+    assert_eq!(status, 400);
+    assert!(body.contains("priority 300 is out of range"), "{body}");
+    assert!(queue.lock().unwrap().is_empty());
+}
+
+#[test]
+fn out_of_range_values_can_be_clamped() {
+    let queue = Arc::new(Mutex::new(QueueManager::new()));
+    let options = ApiOptions::new().with_out_of_range(OutOfRange::Clamp);
+    let server = ApiServer::start_with_options("127.0.0.1:0", Arc::clone(&queue), options).unwrap();
+    let addr = server.local_addr();
+    let now = Utc::now().timestamp();
+
+    let spec = |priority: i64, execution_time: i64| {
+        json!({
+            "execution_time": execution_time,
+            "priority": priority,
+            "description": "Clamped",
+            "function": "email_fn",
+        })
+        .to_string()
+    };
+    let (status, body) = request(addr, "POST", "/jobs", &spec(300, now + 60));
+    assert_eq!(status, 201, "{body}");
+    let (status, body) = request(addr, "POST", "/jobs", &spec(-5, now - 60));
+    assert_eq!(status, 201, "{body}");
+    let (status, body) = request(addr, "POST", "/jobs/validate", &spec(1000, now - 60));
+    assert_eq!(status, 200, "{body}");
+
+    let jobs = queue.lock().unwrap().snapshot();
+    assert_eq!(jobs.len(), 2);
+    assert_eq!(jobs[0].priority, 0);
+    assert!(jobs[0].execution_time >= now);
+    assert_eq!(jobs[1].priority, 255);
+    assert_eq!(jobs[1].execution_time, now + 60);
+}
@@ -0,0 +1,108 @@
+use chrono::{TimeZone, Utc};
+use scheduler::cron::CronSchedule;
+use scheduler::job::Job;
+
+fn ts(y: i32, mo: u32, d: u32, h: u32, mi: u32) -> i64 {
+    Utc.with_ymd_and_hms(y, mo, d, h, mi, 0)
+        .unwrap()
+        .timestamp()
+}
+
+fn next(expr: &str, after: i64) -> Option<i64> {
+    CronSchedule::parse(expr).unwrap().next_after(after)
+}
+
+#[test]
+fn every_minute_fires_on_the_next_minute_boundary() {
+    let after = ts(2030, 1, 1, 10, 0) + 30;
+    assert_eq!(next("* * * * *", after), Some(ts(2030, 1, 1, 10, 1)));
+}
+
+#[test]
+fn nightly_schedule_rolls_over_to_the_next_day() {
+    let expr = "30 2 * * *";
+    assert_eq!(
+        next(expr, ts(2030, 1, 1, 1, 0)),
+        Some(ts(2030, 1, 1, 2, 30))
+    );
+    assert_eq!(
+        next(expr, ts(2030, 1, 1, 2, 30)),
+        Some(ts(2030, 1, 2, 2, 30))
+    );
+    assert_eq!(
+        next(expr, ts(2030, 12, 31, 3, 0)),
+        Some(ts(2031, 1, 1, 2, 30))
+    );
+}
+
+#[test]
+fn steps_ranges_and_lists() {
+    assert_eq!(
+        next("*/15 * * * *", ts(2030, 1, 1, 10, 16)),
+        Some(ts(2030, 1, 1, 10, 30))
+    );
+    assert_eq!(
+        next("0 9-17/4 * * *", ts(2030, 1, 1, 13, 1)),
+        Some(ts(2030, 1, 1, 17, 0))
+    );
+    assert_eq!(
+        next("0 0 1,15 * *", ts(2030, 1, 2, 0, 0)),
+        Some(ts(2030, 1, 15, 0, 0))
+    );
+}
+
+#[test]
+fn day_of_week_accepts_seven_as_sunday() {
+    // 2030-01-01 is a Tuesday; the following Sunday is the 6th
+    let sunday = Some(ts(2030, 1, 6, 0, 0));
+    assert_eq!(next("0 0 * * 0", ts(2030, 1, 1, 0, 0)), sunday);
+    assert_eq!(next("0 0 * * 7", ts(2030, 1, 1, 0, 0)), sunday);
+}
+
+#[test]
+fn restricted_day_fields_match_either() {
+    // The 10th, or any Sunday - whichever comes first
+    assert_eq!(
+        next("0 0 10 * 0", ts(2030, 1, 1, 0, 0)),
+        Some(ts(2030, 1, 6, 0, 0))
+    );
+    assert_eq!(
+        next("0 0 10 * 0", ts(2030, 1, 7, 0, 0)),
+        Some(ts(2030, 1, 10, 0, 0))
+    );
+}
+
+#[test]
+fn impossible_dates_never_fire() {
+    assert_eq!(next("0 0 31 2 *", ts(2030, 1, 1, 0, 0)), None);
+}
+
+#[test]
+fn malformed_expressions_are_rejected() {
+    for expr in [
+        "* * * *",
+        "60 * * * *",
+        "*/0 * * * *",
+        "a * * * *",
+        "5-1 * * * *",
+    ] {
+        assert!(CronSchedule::parse(expr).is_err(), "{expr}");
+    }
+}
+
+#[test]
+fn one_shot_jobs_have_no_next_occurrence() {
+    let job = Job::new(Utc::now().timestamp() + 60, 1, "once", "fn").unwrap();
+    assert_eq!(job.next_occurrence(Utc::now().timestamp()), None);
+}
+
+#[test]
+fn with_schedule_validates_the_expression() {
+    let job = Job::new(Utc::now().timestamp() + 60, 1, "nightly", "fn").unwrap();
+    assert!(job.clone().with_schedule("not cron").is_err());
+    let job = job.with_schedule("0 3 * * *").unwrap();
+    assert_eq!(
+        job.next_occurrence(ts(2030, 1, 1, 0, 0)),
+        Some(ts(2030, 1, 1, 3, 0))
+    );
+}
@@ -0,0 +1,94 @@
+use chrono::Utc;
+use scheduler::diagnostics::{self, REDACTED};
+use scheduler::engine::TimePriorityEngine;
+use scheduler::job::Job;
+use scheduler::logging::RecentLogs;
+use scheduler::persistence::PersistenceManager;
+use scheduler::queue::QueueManager;
+use scheduler::worker::{Worker, send_email};
+use serde_json::{Value, json};
+use std::sync::{Arc, Mutex, mpsc};
+
+#[test]
+fn dump_contains_state_and_redacts_payloads() {
+    let queue = Arc::new(Mutex::new(QueueManager::new()));
+    let (tx, _rx) = mpsc::channel();
+    let engine = TimePriorityEngine::new(Arc::clone(&queue), tx);
+    let mut worker = Worker::new();
+    worker.register("email_fn", send_email).unwrap();
+
+    let job = Job::new(Utc::now().timestamp() + 60, 3, "Send report", "email_fn")
+        .unwrap()
+        .with_payload(json!({ "to": "ops@example.com", "auth": { "password": "hunter2" } }));
+    let id = job.id;
+    queue.lock().unwrap().push(job);
+    let logs = RecentLogs::new(2);
+    for line in ["first", "second", "third"] {
+        logs.push(line);
+    }
+
+    let path = std::env::temp_dir().join(format!("scheduler_diag_{}.json", uuid::Uuid::new_v4()));
+    diagnostics::dump_diagnostics(
+        &path,
+        &queue.lock().unwrap(),
+        &engine,
+        &worker,
+        &logs,
+        &["password"],
+    )
+    .unwrap();
+    let dump: Value = serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    for key in [
+        "generated_at",
+        "queue",
+        "dead_letters",
+        "engine",
+        "worker",
+        "config",
+        "logs",
+    ] {
+        assert!(dump.get(key).is_some(), "missing {key}");
+    }
+    assert_eq!(dump["engine"]["ordering"]["mode"], "TimeFirst");
+    assert_eq!(dump["worker"]["functions"], json!(["email_fn"]));
+    assert_eq!(dump["config"]["engine"]["poll_interval_ms"], 500);
+    assert_eq!(dump["config"]["engine"]["dry_run"], false);
+    assert_eq!(dump["config"]["queue"]["capacity"], Value::Null);
+    assert_eq!(dump["config"]["worker"]["threads"], 1);
+    assert_eq!(dump["logs"], json!(["second", "third"]));
+    let queued = &dump["queue"][0];
+    assert_eq!(queued["id"], json!(id));
+    assert_eq!(queued["payload"]["to"], "ops@example.com");
+    assert_eq!(queued["payload"]["auth"]["password"], REDACTED);
+}
+
+#[test]
+fn self_test_reports_broken_wiring() {
+    let path = std::env::temp_dir()
+        .join(format!("scheduler_missing_{}", uuid::Uuid::new_v4()))
+        .join("queue.json");
+    let store = PersistenceManager::new(&path);
+    let queue = Arc::new(Mutex::new(
+        QueueManager::with_store(Box::new(store)).unwrap(),
+    ));
+    let (tx, _rx) = mpsc::channel();
+    let engine = TimePriorityEngine::new(Arc::clone(&queue), tx);
+    let (log_tx, log_rx) = mpsc::channel();
+    let mut worker = Worker::new().with_log_sink(log_tx);
+    worker.register("email_fn", send_email).unwrap();
+    engine.start();
+
+    let problems = diagnostics::self_test(&queue.lock().unwrap(), &engine, &worker).unwrap_err();
+    assert_eq!(problems.len(), 1, "{problems:?}");
+    assert!(
+        problems[0].starts_with("persistence is not writable"),
+        "{problems:?}"
+    );
+
+    engine.stop();
+    drop(log_rx);
+    let problems = diagnostics::self_test(&queue.lock().unwrap(), &engine, &worker).unwrap_err();
+    assert_eq!(problems.len(), 3, "{problems:?}");
+}
@@ -1,33 +1,895 @@
 use chrono::Utc;
+use scheduler::worker::Worker;
 use scheduler::{engine::TimePriorityEngine, job::Job, queue::QueueManager};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::Sender;
 use std::sync::{Arc, Mutex, mpsc};
 use std::time::Duration;
 
 #[test]
 fn engine_dispatches_jobs_on_time() {
+    use scheduler::clock::MockClock;
+
     let queue = Arc::new(Mutex::new(QueueManager::new()));
     let (tx, rx) = mpsc::channel();
-    let engine = TimePriorityEngine::new(Arc::clone(&queue), tx);
-
-    let now = Utc::now().timestamp();
+    let (trigger_tx, trigger_rx) = mpsc::channel();
+    let (ticks_tx, ticks_rx) = mpsc::channel();
+    let clock = MockClock::new(1_700_000_000);
+    let engine = TimePriorityEngine::new(Arc::clone(&queue), tx)
+        .with_clock(clock.clone())
+        .with_trigger(trigger_rx, false)
+        .with_on_tick(move |_, ready| {
+            let _ = ticks_tx.send(ready);
+        });
 
+    let now = 1_700_000_000;
     {
         let mut q = queue.lock().unwrap();
         // A job exactly at 'now'
-        q.push(Job::new(now, 1, "now job", "fn").unwrap());
+        q.push(Job::new_with_clock(now, 1, "now job", "fn", &clock).unwrap());
         // A job 1 second in the future
-        q.push(Job::new(now + 1, 1, "future job", "fn").unwrap());
+        q.push(Job::new_with_clock(now + 1, 1, "future job", "fn", &clock).unwrap());
     }
 
     engine.start();
+    let tick = || {
+        trigger_tx.send(()).unwrap();
+        ticks_rx.recv_timeout(Duration::from_secs(1)).unwrap()
+    };
 
-    // The 'now' job should arrive almost immediately (within 1 second)
+    // The 'now' job is due straight away; the hook runs just before dispatch
+    assert_eq!(tick(), 1);
     let job1 = rx.recv_timeout(Duration::from_secs(1)).unwrap();
     assert_eq!(job1.description, "now job");
 
-    // The 'future' job should arrive after its time point (we give it up to 2 seconds to account for sleeping)
-    let job2 = rx.recv_timeout(Duration::from_secs(2)).unwrap();
+    // The 'future' job isn't due until the clock reaches its time
+    assert_eq!(tick(), 0);
+    clock.advance(Duration::from_secs(1));
+    assert_eq!(tick(), 1);
+    let job2 = rx.recv_timeout(Duration::from_secs(1)).unwrap();
     assert_eq!(job2.description, "future job");
 
     engine.stop();
 }
+
+#[test]
+fn weighted_selector_orders_ties_reproducibly() {
+    use scheduler::engine::WeightedSelector;
+    use std::collections::HashMap;
+
+    let t = Utc::now().timestamp() + 10;
+    let ties = || -> Vec<Job> {
+        (0..20)
+            .map(|i| {
+                let function = if i % 2 == 0 { "heavy" } else { "light" };
+                Job::new(t, 1, format!("job {i}"), function).unwrap()
+            })
+            .collect()
+    };
+    let weights = HashMap::from([("heavy".to_string(), 9), ("light".to_string(), 1)]);
+
+    let first: Vec<String> = WeightedSelector::new(weights.clone(), 42)
+        .order(ties())
+        .into_iter()
+        .map(|j| j.description)
+        .collect();
+    let second: Vec<String> = WeightedSelector::new(weights.clone(), 42)
+        .order(ties())
+        .into_iter()
+        .map(|j| j.description)
+        .collect();
+    assert_eq!(first, second, "same seed must give the same order");
+
+    // Over many tied groups the heavily weighted function should lead most of the time.
+    let mut selector = WeightedSelector::new(weights, 7);
+    let heavy_first = (0..200)
+        .filter(|_| selector.order(ties())[0].function == "heavy")
+        .count();
+    assert!(heavy_first > 150, "heavy led only {heavy_first}/200 times");
+}
+
+#[test]
+fn weighted_selector_keeps_non_tied_jobs_in_order() {
+    use scheduler::engine::WeightedSelector;
+    use std::collections::HashMap;
+
+    let t = Utc::now().timestamp() + 10;
+    let jobs = vec![
+        Job::new(t, 9, "first", "light").unwrap(),
+        Job::new(t, 1, "second", "heavy").unwrap(),
+        Job::new(t + 1, 9, "third", "heavy").unwrap(),
+    ];
+    let weights = HashMap::from([("heavy".to_string(), 100)]);
+    let ordered = WeightedSelector::new(weights, 1).order(jobs);
+    let names: Vec<&str> = ordered.iter().map(|j| j.description.as_str()).collect();
+    assert_eq!(names, ["first", "second", "third"]);
+}
+
+#[test]
+fn weighted_selector_does_not_shuffle_across_milliseconds() {
+    use scheduler::engine::WeightedSelector;
+    use std::collections::HashMap;
+
+    let t = (Utc::now().timestamp() + 10) * 1000;
+    let weights = HashMap::from([("heavy".to_string(), 1000)]);
+    for seed in 0..20 {
+        let jobs = vec![
+            Job::new_at_millis(t + 100, 5, "first", "light").unwrap(),
+            Job::new_at_millis(t + 900, 5, "second", "heavy").unwrap(),
+        ];
+        let ordered = WeightedSelector::new(weights.clone(), seed).order(jobs);
+        let names: Vec<&str> = ordered.iter().map(|j| j.description.as_str()).collect();
+        assert_eq!(names, ["first", "second"], "seed {seed}");
+    }
+}
+
+#[test]
+fn engine_only_dispatches_allowed_functions() {
+    let queue = Arc::new(Mutex::new(QueueManager::new()));
+    let (tx, rx) = mpsc::channel();
+    let engine =
+        TimePriorityEngine::new(Arc::clone(&queue), tx).with_allowed_functions(["email_fn"]);
+
+    let now = Utc::now().timestamp();
+    {
+        let mut q = queue.lock().unwrap();
+        q.push(Job::new(now, 1, "email", "email_fn").unwrap());
+        q.push(Job::new(now, 1, "backup", "backup_fn").unwrap());
+        q.push(Job::new(now, 1, "another email", "email_fn").unwrap());
+    }
+
+    engine.start();
+    let mut dispatched: Vec<String> = Vec::new();
+    while let Ok(job) = rx.recv_timeout(Duration::from_secs(1)) {
+        dispatched.push(job.function);
+    }
+    engine.stop();
+
+    assert_eq!(dispatched, ["email_fn", "email_fn"]);
+    let q = queue.lock().unwrap();
+    assert_eq!(q.len(), 1);
+    assert_eq!(q.peek().unwrap().function, "backup_fn");
+}
+
+fn startup_dispatch_order(policy: scheduler::engine::StartupPolicy) -> Vec<String> {
+    let queue = Arc::new(Mutex::new(QueueManager::new()));
+    let (tx, rx) = mpsc::channel();
+    let engine = TimePriorityEngine::new(Arc::clone(&queue), tx).with_startup_policy(policy);
+
+    let now = Utc::now().timestamp();
+    {
+        let mut q = queue.lock().unwrap();
+        for (desc, overdue_by) in [("backlog 1", 120), ("backlog 2", 60)] {
+            let mut job = Job::new(now, 1, desc, "fn").unwrap();
+            job.execution_time = now - overdue_by;
+            q.push(job);
+        }
+        q.push(Job::new(now + 1, 1, "real-time", "fn").unwrap());
+    }
+
+    engine.start();
+    let order = (0..3)
+        .map(|_| rx.recv_timeout(Duration::from_secs(4)).unwrap().description)
+        .collect();
+    engine.stop();
+    order
+}
+
+#[test]
+fn startup_policy_controls_backlog_order() {
+    use scheduler::engine::StartupPolicy;
+
+    assert_eq!(
+        startup_dispatch_order(StartupPolicy::CatchUpFirst),
+        ["backlog 1", "backlog 2", "real-time"]
+    );
+    assert_eq!(
+        startup_dispatch_order(StartupPolicy::RealTimeFirst { ticks: 6 }),
+        ["real-time", "backlog 1", "backlog 2"]
+    );
+}
+
+#[test]
+fn engine_with_trigger_dispatches_only_on_ticks() {
+    let queue = Arc::new(Mutex::new(QueueManager::new()));
+    let (tx, rx) = mpsc::channel();
+    let (tick_tx, tick_rx) = mpsc::channel();
+    let engine = TimePriorityEngine::new(Arc::clone(&queue), tx).with_trigger(tick_rx, false);
+
+    let now = Utc::now().timestamp();
+    queue
+        .lock()
+        .unwrap()
+        .push(Job::new(now, 1, "due job", "fn").unwrap());
+
+    engine.start();
+    assert!(
+        rx.recv_timeout(Duration::from_millis(1200)).is_err(),
+        "nothing should be dispatched without a tick"
+    );
+
+    tick_tx.send(()).unwrap();
+    let job = rx.recv_timeout(Duration::from_millis(300)).unwrap();
+    assert_eq!(job.description, "due job");
+
+    engine.stop();
+}
+
+#[test]
+fn shortest_job_first_breaks_ties_by_estimate() {
+    let queue = Arc::new(Mutex::new(QueueManager::new()));
+    let (tx, rx) = mpsc::channel();
+    let engine = TimePriorityEngine::new(Arc::clone(&queue), tx).with_shortest_job_first(300);
+
+    let now = Utc::now().timestamp();
+    {
+        let mut q = queue.lock().unwrap();
+        for (desc, estimate) in [("slow", Some(500)), ("unknown", None), ("quick", Some(100))] {
+            let mut job = Job::new(now, 1, desc, "fn").unwrap();
+            job.estimated_duration_ms = estimate;
+            q.push(job);
+        }
+    }
+
+    engine.start();
+    let order: Vec<String> = (0..3)
+        .map(|_| rx.recv_timeout(Duration::from_secs(1)).unwrap().description)
+        .collect();
+    engine.stop();
+
+    assert_eq!(order, ["quick", "unknown", "slow"]);
+}
+
+#[test]
+fn engine_reenqueues_recurring_jobs_at_next_occurrence() {
+    let queue = Arc::new(Mutex::new(QueueManager::new()));
+    let (tx, rx) = mpsc::channel();
+    let engine = TimePriorityEngine::new(Arc::clone(&queue), tx);
+
+    let now = Utc::now().timestamp();
+    let mut recurring = Job::new(now, 1, "every minute", "fn")
+        .unwrap()
+        .with_schedule("* * * * *")
+        .unwrap();
+    // Long overdue: the next run must still land in the future
+    recurring.execution_time = now - 3600;
+    let id = recurring.id;
+    {
+        let mut q = queue.lock().unwrap();
+        q.push(recurring);
+        q.push(Job::new(now, 1, "one-shot", "fn").unwrap());
+    }
+
+    engine.start();
+    let first = rx.recv_timeout(Duration::from_secs(1)).unwrap();
+    let second = rx.recv_timeout(Duration::from_secs(1)).unwrap();
+    engine.stop();
+
+    assert_eq!(first.id, id);
+    assert_eq!(second.description, "one-shot");
+    let q = queue.lock().unwrap();
+    assert_eq!(q.len(), 1);
+    let next = q.peek().unwrap();
+    assert_ne!(next.id, id);
+    assert_eq!(next.series_id, Some(id));
+    assert!(next.execution_time > now && next.execution_time <= now + 60);
+    assert_eq!(next.execution_time % 60, 0);
+}
+
+#[test]
+fn retrying_a_recurring_run_keeps_its_next_occurrence() {
+    fn always_fails(_: &Job, _: Sender<String>) -> Result<(), String> {
+        Err("upstream down".to_string())
+    }
+
+    let queue = Arc::new(Mutex::new(QueueManager::new()));
+    let (tx, rx) = mpsc::channel();
+    let engine = TimePriorityEngine::new(Arc::clone(&queue), tx);
+    let mut worker = Worker::new().with_queue(Arc::clone(&queue));
+    worker.register("fails", always_fails).unwrap();
+
+    let now = Utc::now().timestamp();
+    let recurring = Job::new(now, 1, "every minute", "fails")
+        .unwrap()
+        .with_schedule("* * * * *")
+        .unwrap()
+        .with_max_retries(1)
+        .with_retry_policy(scheduler::job::RetryPolicy::FixedDelay(
+            Duration::from_secs(3600),
+        ));
+    let id = recurring.id;
+    queue.lock().unwrap().push(recurring);
+
+    engine.start();
+    let mut first = rx.recv_timeout(Duration::from_secs(1)).unwrap();
+    engine.stop();
+    worker.run_job(&mut first);
+
+    let q = queue.lock().unwrap();
+    assert_eq!(
+        q.len(),
+        2,
+        "both the retry and the next occurrence are queued"
+    );
+    let jobs = q.snapshot();
+    let next = jobs.iter().find(|j| j.id != id).unwrap();
+    assert_eq!(next.series_id, Some(id));
+    assert_eq!(next.retry_count, 0);
+    let retry = jobs.iter().find(|j| j.id == id).unwrap();
+    assert_eq!(retry.retry_count, 1);
+}
+
+#[test]
+fn strict_priority_dispatches_high_priority_first_among_due_jobs() {
+    use scheduler::engine::SchedulerMode;
+
+    let queue = Arc::new(Mutex::new(QueueManager::new()));
+    let (tx, rx) = mpsc::channel();
+    let engine =
+        TimePriorityEngine::new(Arc::clone(&queue), tx).with_mode(SchedulerMode::StrictPriority);
+
+    let now = Utc::now().timestamp();
+    {
+        let mut q = queue.lock().unwrap();
+        let mut earlier = Job::new(now, 1, "low priority, earlier", "fn").unwrap();
+        earlier.execution_time = now - 30;
+        q.push(earlier);
+        let mut later = Job::new(now, 9, "high priority, later", "fn").unwrap();
+        later.execution_time = now - 5;
+        q.push(later);
+    }
+
+    engine.start();
+    let first = rx.recv_timeout(Duration::from_secs(1)).unwrap();
+    let second = rx.recv_timeout(Duration::from_secs(1)).unwrap();
+    engine.stop();
+
+    assert_eq!(first.description, "high priority, later");
+    assert_eq!(second.description, "low priority, earlier");
+}
+
+#[test]
+fn fair_share_interleaves_groups_of_due_jobs() {
+    use scheduler::engine::Strategy;
+    use uuid::Uuid;
+
+    let queue = Arc::new(Mutex::new(QueueManager::new()));
+    let (tx, _rx) = mpsc::channel();
+    let engine = TimePriorityEngine::new(Arc::clone(&queue), tx).with_strategy(Strategy::FairShare);
+
+    let now = Utc::now().timestamp();
+    let tenant = |group: &str, priority| {
+        Job::new(now + 100, priority, group, "fn")
+            .unwrap()
+            .with_group(group)
+    };
+    // The busy tenant's jobs are queued first and outrank the others
+    let jobs = [
+        tenant("busy", 9),
+        tenant("busy", 9),
+        tenant("busy", 9),
+        tenant("quiet", 1),
+        tenant("quiet", 1),
+        Job::new(now + 100, 1, "ungrouped", "fn").unwrap(),
+    ];
+    let ids: Vec<Uuid> = jobs.iter().map(|j| j.id).collect();
+    {
+        let mut q = queue.lock().unwrap();
+        for job in jobs {
+            q.push(job);
+        }
+    }
+
+    let fired: Vec<Uuid> = engine
+        .simulate(now + 1000)
+        .into_iter()
+        .map(|(_, id)| id)
+        .collect();
+    assert_eq!(fired, [ids[0], ids[3], ids[5], ids[1], ids[4], ids[2]]);
+}
+
+#[test]
+fn fair_share_rotates_groups_under_an_in_flight_limit() {
+    use scheduler::engine::Strategy;
+
+    let queue = Arc::new(Mutex::new(QueueManager::new()));
+    let (tx, rx) = mpsc::channel();
+    let engine = TimePriorityEngine::new(Arc::clone(&queue), tx)
+        .with_strategy(Strategy::FairShare)
+        .with_max_in_flight(1);
+    let completions = engine.completion_sender();
+
+    // Only one job leaves per tick, so the rotation has to carry over
+    let now = Utc::now().timestamp();
+    {
+        let mut q = queue.lock().unwrap();
+        for group in ["a", "a", "a", "a", "b", "b"] {
+            q.push(Job::new(now, 1, group, "fn").unwrap().with_group(group));
+        }
+    }
+    engine.start();
+    let mut groups = Vec::new();
+    for _ in 0..6 {
+        let job: Job = rx.recv_timeout(Duration::from_secs(1)).unwrap();
+        groups.push(job.group.unwrap());
+        completions.send(job.id);
+    }
+    engine.stop();
+
+    assert_eq!(groups, ["a", "b", "a", "b", "a", "a"]);
+}
+
+#[test]
+fn ordering_info_reports_configured_mode() {
+    use scheduler::engine::{OrderingInfo, SchedulerMode, Strategy};
+
+    let queue = Arc::new(Mutex::new(QueueManager::new()));
+    let (tx, _rx) = mpsc::channel();
+    let engine = TimePriorityEngine::new(Arc::clone(&queue), tx)
+        .with_mode(SchedulerMode::StrictPriority)
+        .with_shortest_job_first(250);
+
+    assert_eq!(
+        engine.ordering_info(),
+        OrderingInfo {
+            mode: SchedulerMode::StrictPriority,
+            strategy: Strategy::TimePriority,
+            shortest_job_first_default_ms: Some(250),
+            function_weights: None,
+            weights_seed: None,
+            priority_aging_secs: None,
+        }
+    );
+}
+
+#[test]
+fn short_poll_interval_dispatches_promptly() {
+    let queue = Arc::new(Mutex::new(QueueManager::new()));
+    let (tx, rx) = mpsc::channel();
+    let engine = TimePriorityEngine::new(Arc::clone(&queue), tx)
+        .with_poll_interval(Duration::from_millis(20));
+    assert_eq!(engine.poll_interval(), Duration::from_millis(20));
+
+    // Execution times are whole seconds, so aim for the next second boundary
+    let due = Utc::now().timestamp() + 1;
+    queue
+        .lock()
+        .unwrap()
+        .push(Job::new(due, 1, "next second", "fn").unwrap());
+
+    engine.start();
+    rx.recv_timeout(Duration::from_secs(2)).unwrap();
+    let late_by = Utc::now().timestamp_millis() - due * 1000;
+    engine.stop();
+
+    assert!(late_by < 150, "dispatched {late_by}ms after its time");
+}
+
+#[test]
+fn idle_engine_sleeps_until_a_job_is_pushed() {
+    let queue = Arc::new(Mutex::new(QueueManager::new()));
+    let (tx, rx) = mpsc::channel();
+    let engine = TimePriorityEngine::new(Arc::clone(&queue), tx);
+
+    engine.start();
+    std::thread::sleep(Duration::from_secs(1));
+    assert!(
+        engine.wakeups() <= 1,
+        "empty engine woke {} times",
+        engine.wakeups()
+    );
+
+    // Pushed mid-sleep: the engine must wake and fire it at its time
+    let due = Utc::now().timestamp() + 1;
+    queue
+        .lock()
+        .unwrap()
+        .push(Job::new(due, 1, "mid-sleep job", "fn").unwrap());
+    let job = rx.recv_timeout(Duration::from_secs(2)).unwrap();
+    let late_by = Utc::now().timestamp_millis() - due * 1000;
+    engine.stop();
+
+    assert_eq!(job.description, "mid-sleep job");
+    assert!(
+        (0..100).contains(&late_by),
+        "dispatched {late_by}ms after its time"
+    );
+    assert!(engine.wakeups() <= 4);
+}
+
+#[test]
+fn engine_renders_dispatch_lines_with_custom_formatter() {
+    use scheduler::logging::{DefaultFormatter, LogEvent, LogFormatter};
+
+    struct Recording(Mutex<mpsc::Sender<String>>);
+    impl LogFormatter for Recording {
+        fn format(&self, event: &LogEvent) -> String {
+            let line = match event {
+                LogEvent::Dispatched(job) => format!("dispatch id={} fn={}", job.id, job.function),
+                other => DefaultFormatter.format(other),
+            };
+            self.0.lock().unwrap().send(line.clone()).unwrap();
+            line
+        }
+    }
+
+    let queue = Arc::new(Mutex::new(QueueManager::new()));
+    let (tx, rx) = mpsc::channel();
+    let (log_tx, log_rx) = mpsc::channel();
+    let engine = TimePriorityEngine::new(Arc::clone(&queue), tx)
+        .with_log_formatter(Recording(Mutex::new(log_tx)));
+
+    let job = Job::new(Utc::now().timestamp(), 1, "formatted", "report").unwrap();
+    let id = job.id;
+    assert!(
+        DefaultFormatter
+            .format(&LogEvent::Dispatched(&job))
+            .starts_with(&format!("[Engine] Job {} ('formatted')", job.short_id()))
+    );
+    queue.lock().unwrap().push(job);
+
+    engine.start();
+    rx.recv_timeout(Duration::from_secs(1)).unwrap();
+    engine.stop();
+
+    let line = log_rx.recv_timeout(Duration::from_secs(1)).unwrap();
+    assert_eq!(line, format!("dispatch id={} fn=report", id));
+}
+
+#[test]
+fn paused_engine_holds_due_jobs_until_resumed() {
+    let queue = Arc::new(Mutex::new(QueueManager::new()));
+    let (tx, rx) = mpsc::channel();
+    let engine = TimePriorityEngine::new(Arc::clone(&queue), tx);
+
+    engine.start();
+    engine.pause();
+    assert!(engine.is_paused());
+    let due = Utc::now().timestamp() + 1;
+    queue
+        .lock()
+        .unwrap()
+        .push(Job::new(due, 1, "during deploy", "fn").unwrap());
+
+    // The job's time passes while paused
+    assert!(rx.recv_timeout(Duration::from_millis(2200)).is_err());
+    assert_eq!(queue.lock().unwrap().len(), 1);
+
+    engine.resume();
+    let job = rx.recv_timeout(Duration::from_millis(200)).unwrap();
+    engine.stop();
+    assert_eq!(job.description, "during deploy");
+}
+
+static RUNNING: AtomicUsize = AtomicUsize::new(0);
+static PEAK: AtomicUsize = AtomicUsize::new(0);
+
+fn rate_limited_call(_: &Job, done: Sender<String>) -> Result<(), String> {
+    let now_running = RUNNING.fetch_add(1, Ordering::SeqCst) + 1;
+    PEAK.fetch_max(now_running, Ordering::SeqCst);
+    std::thread::sleep(Duration::from_millis(200));
+    RUNNING.fetch_sub(1, Ordering::SeqCst);
+    let _ = done.send("done".to_string());
+    Ok(())
+}
+
+#[test]
+fn engine_never_exceeds_max_in_flight() {
+    let queue = Arc::new(Mutex::new(QueueManager::new()));
+    let (tx, rx) = mpsc::channel();
+    let engine = TimePriorityEngine::new(Arc::clone(&queue), tx).with_max_in_flight(2);
+
+    let (done_tx, done_rx) = mpsc::channel();
+    let mut worker = Worker::new()
+        .with_threads(5)
+        .with_log_sink(done_tx)
+        .with_completions(engine.completion_sender());
+    worker.register("api_call", rate_limited_call).unwrap();
+    let pool = std::thread::spawn(move || worker.start(rx));
+
+    let now = Utc::now().timestamp();
+    for i in 0..5 {
+        queue
+            .lock()
+            .unwrap()
+            .push(Job::new(now, 1, format!("call {i}"), "api_call").unwrap());
+    }
+    engine.start();
+    for _ in 0..5 {
+        done_rx.recv_timeout(Duration::from_secs(3)).unwrap();
+    }
+    engine.stop();
+    drop(engine);
+    pool.join().unwrap();
+
+    assert_eq!(PEAK.load(Ordering::SeqCst), 2);
+    assert!(queue.lock().unwrap().is_empty());
+}
+
+#[test]
+fn on_tick_reports_each_iteration() {
+    let queue = Arc::new(Mutex::new(QueueManager::new()));
+    let (tx, _rx) = mpsc::channel();
+    let (trigger_tx, trigger_rx) = mpsc::channel();
+    let (ticks_tx, ticks_rx) = mpsc::channel();
+    let engine = TimePriorityEngine::new(Arc::clone(&queue), tx)
+        .with_trigger(trigger_rx, false)
+        .with_on_tick(move |now, ready| {
+            let _ = ticks_tx.send((now, ready));
+        });
+
+    let now = Utc::now().timestamp();
+    {
+        let mut q = queue.lock().unwrap();
+        q.push(Job::new(now, 1, "a", "fn").unwrap());
+        q.push(Job::new(now, 1, "b", "fn").unwrap());
+    }
+    engine.start();
+    trigger_tx.send(()).unwrap();
+    let (first_now, first_ready) = ticks_rx.recv_timeout(Duration::from_secs(1)).unwrap();
+
+    std::thread::sleep(Duration::from_millis(1100));
+    queue
+        .lock()
+        .unwrap()
+        .push(Job::new(Utc::now().timestamp(), 1, "c", "fn").unwrap());
+    trigger_tx.send(()).unwrap();
+    let (second_now, second_ready) = ticks_rx.recv_timeout(Duration::from_secs(1)).unwrap();
+    engine.stop();
+
+    assert_eq!(first_ready, 2);
+    assert_eq!(second_ready, 1);
+    assert!(second_now > first_now);
+}
+
+#[test]
+fn conditions_gate_dispatch() {
+    use scheduler::engine::ConditionPolicy;
+    use scheduler::job::Status;
+    use scheduler::persistence::InMemoryStore;
+
+    let store = Arc::new(InMemoryStore::new());
+    let queue = Arc::new(Mutex::new(
+        QueueManager::with_store(Box::new(Arc::clone(&store))).unwrap(),
+    ));
+    let (tx, rx) = mpsc::channel();
+    let mut engine = TimePriorityEngine::new(Arc::clone(&queue), tx)
+        .with_condition_policy(ConditionPolicy::Skip);
+    engine.register_condition("disk_ok", || true);
+    engine.register_condition("disk_full", || false);
+
+    let now = Utc::now().timestamp();
+    let blocked = Job::new(now, 1, "blocked", "fn")
+        .unwrap()
+        .with_condition("disk_full");
+    let blocked_id = blocked.id;
+    {
+        let mut q = queue.lock().unwrap();
+        q.push(blocked);
+        q.push(
+            Job::new(now, 1, "allowed", "fn")
+                .unwrap()
+                .with_condition("disk_ok"),
+        );
+    }
+    engine.start();
+    let dispatched = rx.recv_timeout(Duration::from_secs(1)).unwrap();
+    let extra = rx.recv_timeout(Duration::from_millis(300));
+    engine.stop();
+
+    assert_eq!(dispatched.description, "allowed");
+    assert!(extra.is_err());
+    let q = queue.lock().unwrap();
+    assert!(q.is_empty());
+    // The skipped run is finished, not left looking like it's running
+    assert_eq!(q.status_of(blocked_id), Some(Status::Cancelled));
+    let history = q.history(10);
+    assert_eq!(history.len(), 1);
+    assert_eq!(history[0].id, blocked_id);
+    assert_eq!(history[0].status, Status::Cancelled);
+}
+
+#[test]
+fn unmet_condition_reschedules_by_default() {
+    let queue = Arc::new(Mutex::new(QueueManager::new()));
+    let (tx, rx) = mpsc::channel();
+    let mut engine = TimePriorityEngine::new(Arc::clone(&queue), tx);
+    engine.register_condition("never", || false);
+
+    let now = Utc::now().timestamp();
+    queue.lock().unwrap().push(
+        Job::new(now, 1, "waiting", "fn")
+            .unwrap()
+            .with_condition("never"),
+    );
+    engine.start();
+    let dispatched = rx.recv_timeout(Duration::from_millis(300));
+    engine.stop();
+
+    assert!(dispatched.is_err());
+    let q = queue.lock().unwrap();
+    assert!(q.peek().unwrap().execution_time >= now + 60);
+}
+
+#[test]
+fn stop_timeout_gives_up_on_a_wedged_loop() {
+    let queue = Arc::new(Mutex::new(QueueManager::new()));
+    let (tx, _rx) = mpsc::channel();
+    let (entered_tx, entered_rx) = mpsc::channel();
+    let (release_tx, release_rx) = mpsc::channel::<()>();
+    let engine = TimePriorityEngine::new(Arc::clone(&queue), tx).with_on_tick(move |_, _| {
+        let _ = entered_tx.send(());
+        // Wedge the dispatch loop until the test releases it
+        let _ = release_rx.recv();
+    });
+
+    engine.start();
+    entered_rx.recv_timeout(Duration::from_secs(1)).unwrap();
+    let started = std::time::Instant::now();
+    assert!(!engine.stop_timeout(Duration::from_millis(200)));
+    assert!(started.elapsed() < Duration::from_secs(1));
+
+    drop(release_tx);
+    let idle = TimePriorityEngine::new(queue, mpsc::channel().0);
+    idle.start();
+    assert!(idle.stop_timeout(Duration::from_secs(1)));
+}
+
+#[test]
+fn priority_aging_lets_overdue_low_priority_job_go_first() {
+    use scheduler::engine::SchedulerMode;
+
+    let queue = Arc::new(Mutex::new(QueueManager::new()));
+    let (tx, rx) = mpsc::channel();
+    let engine = TimePriorityEngine::new(Arc::clone(&queue), tx)
+        .with_mode(SchedulerMode::StrictPriority)
+        .with_priority_aging(10);
+
+    let now = Utc::now().timestamp();
+    {
+        let mut q = queue.lock().unwrap();
+        let mut waiting = Job::new(now, 1, "overdue low priority", "fn").unwrap();
+        waiting.execution_time = now - 60;
+        q.push(waiting);
+        q.push(Job::new(now, 5, "fresh high priority", "fn").unwrap());
+    }
+
+    engine.start();
+    let first = rx.recv_timeout(Duration::from_secs(1)).unwrap();
+    let second = rx.recv_timeout(Duration::from_secs(1)).unwrap();
+    engine.stop();
+
+    assert_eq!(first.description, "overdue low priority");
+    assert_eq!(second.description, "fresh high priority");
+}
+
+#[test]
+fn sub_second_job_fires_on_its_millisecond() {
+    let queue = Arc::new(Mutex::new(QueueManager::new()));
+    let (tx, rx) = mpsc::channel();
+    let engine = TimePriorityEngine::new(Arc::clone(&queue), tx);
+
+    let started = std::time::Instant::now();
+    let due = Utc::now().timestamp_millis() + 250;
+    queue
+        .lock()
+        .unwrap()
+        .push(Job::new_at_millis(due, 1, "quarter second", "fn").unwrap());
+    engine.start();
+    let job = rx.recv_timeout(Duration::from_secs(2)).unwrap();
+    let elapsed = started.elapsed();
+    engine.stop();
+
+    assert_eq!(job.due_at_ms(), due);
+    assert!(elapsed >= Duration::from_millis(240), "{elapsed:?}");
+    assert!(elapsed < Duration::from_millis(600), "{elapsed:?}");
+}
+
+#[test]
+fn dry_run_leaves_due_jobs_queued() {
+    let queue = Arc::new(Mutex::new(QueueManager::new()));
+    let (tx, rx) = mpsc::channel();
+    let engine = TimePriorityEngine::new(Arc::clone(&queue), tx).with_dry_run();
+
+    let now = Utc::now().timestamp();
+    queue
+        .lock()
+        .unwrap()
+        .push(Job::new(now, 1, "rehearsal", "fn").unwrap());
+    engine.start();
+    let dispatched = rx.recv_timeout(Duration::from_millis(300));
+    engine.stop();
+
+    assert!(dispatched.is_err());
+    assert_eq!(
+        queue.lock().unwrap().peek().unwrap().description,
+        "rehearsal"
+    );
+}
+
+#[test]
+fn dry_run_leaves_jobs_with_unmet_conditions_untouched() {
+    use scheduler::engine::ConditionPolicy;
+    use uuid::Uuid;
+
+    for policy in [
+        ConditionPolicy::Reschedule { secs: 60 },
+        ConditionPolicy::Skip,
+    ] {
+        let queue = Arc::new(Mutex::new(QueueManager::new()));
+        let (tx, rx) = mpsc::channel();
+        let mut engine = TimePriorityEngine::new(Arc::clone(&queue), tx)
+            .with_dry_run()
+            .with_condition_policy(policy);
+        engine.register_condition("never", || false);
+
+        let now = Utc::now().timestamp();
+        let one_shot = Job::new(now, 1, "one-shot", "fn")
+            .unwrap()
+            .with_condition("never");
+        let recurring = Job::new(now, 1, "recurring", "fn")
+            .unwrap()
+            .with_condition("never")
+            .with_schedule("* * * * *")
+            .unwrap();
+        let mut expected: Vec<(Uuid, i64)> = [&one_shot, &recurring]
+            .iter()
+            .map(|j| (j.id, j.execution_time))
+            .collect();
+        {
+            let mut q = queue.lock().unwrap();
+            q.push(one_shot);
+            q.push(recurring);
+        }
+        engine.start();
+        let dispatched = rx.recv_timeout(Duration::from_millis(300));
+        engine.stop();
+
+        assert!(dispatched.is_err(), "{policy:?}");
+        let mut queued: Vec<(Uuid, i64)> = queue
+            .lock()
+            .unwrap()
+            .snapshot()
+            .iter()
+            .map(|j| (j.id, j.execution_time))
+            .collect();
+        queued.sort();
+        expected.sort();
+        assert_eq!(queued, expected, "{policy:?}");
+    }
+}
+
+#[test]
+fn simulate_reports_firing_order_without_running() {
+    use scheduler::engine::SchedulerMode;
+
+    let queue = Arc::new(Mutex::new(QueueManager::new()));
+    let (tx, rx) = mpsc::channel();
+    let engine =
+        TimePriorityEngine::new(Arc::clone(&queue), tx).with_mode(SchedulerMode::StrictPriority);
+
+    let now = Utc::now().timestamp();
+    let low = Job::new(now + 100, 1, "low", "fn").unwrap();
+    let high = Job::new(now + 100, 9, "high", "fn").unwrap();
+    let late = Job::new(now + 5000, 1, "after the window", "fn").unwrap();
+    let first = Job::new(now + 10, 1, "first", "fn").unwrap();
+    let (low_id, high_id, first_id) = (low.id, high.id, first.id);
+    {
+        let mut q = queue.lock().unwrap();
+        for job in [low, high, late, first] {
+            q.push(job);
+        }
+    }
+
+    let fired = engine.simulate(now + 1000);
+    assert_eq!(
+        fired,
+        [
+            (now + 10, first_id),
+            (now + 100, high_id),
+            (now + 100, low_id)
+        ]
+    );
+    assert_eq!(queue.lock().unwrap().len(), 4);
+    assert!(rx.try_recv().is_err());
+}
@@ -0,0 +1,204 @@
+use scheduler::job::{Status, StatusCase};
+
+#[test]
+fn status_round_trips_in_every_case() {
+    for case in [StatusCase::Pascal, StatusCase::Lower, StatusCase::Upper] {
+        for status in Status::ALL {
+            let text = status.to_case(case);
+            assert_eq!(text.parse::<Status>().unwrap(), status, "{text}");
+
+            let json = format!("\"{}\"", text);
+            let decoded: Status = serde_json::from_str(&json).unwrap();
+            assert_eq!(decoded, status, "{json}");
+        }
+    }
+}
+
+#[test]
+fn status_serializes_as_variant_name() {
+    assert_eq!(
+        serde_json::to_string(&Status::Pending).unwrap(),
+        "\"Pending\""
+    );
+    assert_eq!(Status::Failed.to_case(StatusCase::Lower), "failed");
+    assert_eq!(Status::Running.to_case(StatusCase::Upper), "RUNNING");
+}
+
+#[test]
+fn unknown_status_is_rejected() {
+    assert!("done".parse::<Status>().is_err());
+}
+
+#[test]
+fn job_without_payload_field_loads_as_null() {
+    use scheduler::job::Job;
+
+    let json = r#"{
+        "id": "67e55044-10b1-426f-9247-bb680e5fe0c8",
+        "execution_time": 0,
+        "priority": 1,
+        "description": "legacy",
+        "function": "fn",
+        "status": "Pending"
+    }"#;
+    let job: Job = serde_json::from_str(json).unwrap();
+    assert_eq!(job.payload, serde_json::Value::Null);
+
+    let with_payload = job.with_payload(serde_json::json!({ "to": "ops@example.com" }));
+    let round_trip: Job =
+        serde_json::from_str(&serde_json::to_string(&with_payload).unwrap()).unwrap();
+    assert_eq!(round_trip.payload["to"], "ops@example.com");
+}
+
+#[test]
+fn exponential_backoff_doubles_delay_up_to_max() {
+    use scheduler::job::{Job, RetryPolicy};
+    use std::time::Duration;
+
+    let policy = RetryPolicy::ExponentialBackoff {
+        base: Duration::from_secs(1),
+        max: Duration::from_secs(6),
+    };
+    let start = chrono::Utc::now().timestamp();
+    let mut job = Job::new(start, 1, "flaky", "fn")
+        .unwrap()
+        .with_retry_policy(policy);
+    job.max_retries = 5;
+
+    for _ in 0..3 {
+        assert!(job.fail_and_retry());
+    }
+    assert_eq!(job.retry_count, 3);
+    assert_eq!(job.status, Status::Pending);
+    let delay = job.execution_time - start;
+    assert!((4..=5).contains(&delay), "third retry is {delay}s out");
+
+    assert_eq!(policy.delay(4), Duration::from_secs(6));
+    assert_eq!(policy.delay(40), Duration::from_secs(6));
+    assert_eq!(
+        RetryPolicy::FixedDelay(Duration::from_secs(3)).delay(7),
+        Duration::from_secs(3)
+    );
+}
+
+#[test]
+fn status_transitions_follow_the_job_lifecycle() {
+    use Status::*;
+
+    let legal = [
+        (Pending, Running),
+        (Running, Success),
+        (Running, Failed),
+        (Failed, Pending),
+        (Pending, Cancelled),
+        (Running, Cancelled),
+    ];
+    for from in Status::ALL {
+        for to in Status::ALL {
+            let expected = legal.contains(&(from.clone(), to.clone()));
+            assert_eq!(from.can_transition_to(&to), expected, "{from:?} -> {to:?}");
+        }
+    }
+}
+
+#[test]
+fn millisecond_times_split_and_order() {
+    use scheduler::job::Job;
+
+    let at = chrono::Utc::now().timestamp_millis() + 10_250;
+    let later = Job::new_at_millis(at + 500, 1, "later", "fn").unwrap();
+    let sooner = Job::new_at_millis(at, 1, "sooner", "fn").unwrap();
+    assert_eq!(sooner.due_at_ms(), at);
+    assert_eq!(sooner.execution_time, at.div_euclid(1000));
+    assert!(
+        sooner > later,
+        "sooner jobs rank higher, as with whole seconds"
+    );
+    assert!(Job::new_at_millis(at - 20_000, 1, "past", "fn").is_err());
+
+    // Files written before millisecond support have no execution_ms
+    let mut value = serde_json::to_value(&sooner).unwrap();
+    value.as_object_mut().unwrap().remove("execution_ms");
+    let loaded: Job = serde_json::from_value(value).unwrap();
+    assert_eq!(loaded.execution_ms, 0);
+}
+
+#[test]
+fn new_at_parses_rfc3339_with_offsets() {
+    use scheduler::job::Job;
+
+    let utc = Job::new_at("2099-06-01T09:00:00Z", 1, "utc", "fn").unwrap();
+    let offset = Job::new_at("2099-06-01T11:00:00+02:00", 1, "offset", "fn").unwrap();
+    let behind = Job::new_at("2099-06-01T04:30:00-04:30", 1, "behind", "fn").unwrap();
+    assert_eq!(utc.execution_time, 4_083_987_600);
+    assert_eq!(offset.execution_time, utc.execution_time);
+    assert_eq!(behind.execution_time, utc.execution_time);
+
+    for malformed in [
+        "2099-06-01T09:00:00",
+        "2099-06-01 09:00",
+        "2099-13-01T09:00:00Z",
+        "tomorrow",
+        "",
+    ] {
+        let err = Job::new_at(malformed, 1, "bad", "fn").unwrap_err();
+        assert!(err.contains("invalid RFC3339"), "{malformed}: {err}");
+    }
+    let err = Job::new_at("2000-01-01T00:00:00Z", 1, "past", "fn").unwrap_err();
+    assert!(err.contains("in the past"), "{err}");
+}
+
+#[test]
+fn parse_duration_accepts_each_unit_and_combinations() {
+    use scheduler::time_format::parse_duration;
+    use std::time::Duration;
+
+    for (text, secs) in [
+        ("45s", 45),
+        ("5m", 300),
+        ("3h", 10_800),
+        ("2d", 172_800),
+        ("1h30m", 5_400),
+        ("1d2h3m4s", 93_784),
+        ("90m", 5_400),
+        (" 10s ", 10),
+    ] {
+        assert_eq!(
+            parse_duration(text),
+            Ok(Duration::from_secs(secs)),
+            "{text}"
+        );
+    }
+}
+
+#[test]
+fn parse_duration_rejects_past_and_malformed_delays() {
+    use scheduler::time_format::parse_duration;
+
+    for bad in ["0s", "0h0m", "-5m", "", "10", "h", "1w", "1.5h", "1 h"] {
+        let err = parse_duration(bad).unwrap_err();
+        assert!(err.contains("invalid duration"), "{bad}: {err}");
+    }
+}
+
+#[test]
+fn new_in_schedules_relative_to_now() {
+    use scheduler::job::Job;
+
+    let before = chrono::Utc::now().timestamp();
+    let job = Job::new_in("1h30m", 1, "later", "fn").unwrap();
+    let after = chrono::Utc::now().timestamp();
+    assert!((before + 5_400..=after + 5_400).contains(&job.execution_time));
+    assert!(Job::new_in("0s", 1, "now", "fn").is_err());
+}
+
+#[test]
+fn new_in_rejects_delays_past_the_end_of_time() {
+    use scheduler::job::Job;
+
+    // Neither fits once added to now: one isn't even an i64 of seconds
+    for delay in [format!("{}s", u64::MAX), format!("{}s", i64::MAX)] {
+        let err = Job::new_in(&delay, 1, "never", "fn").unwrap_err();
+        assert_eq!(err, format!("delay '{}' is too large", delay));
+    }
+}
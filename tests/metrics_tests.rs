@@ -0,0 +1,74 @@
+#![cfg(feature = "metrics")]
+
+use chrono::Utc;
+use scheduler::api::ApiServer;
+use scheduler::engine::TimePriorityEngine;
+use scheduler::job::Job;
+use scheduler::metrics::Metrics;
+use scheduler::queue::QueueManager;
+use scheduler::worker::Worker;
+use std::io::{Read, Write};
+use std::net::TcpStream;
+use std::sync::mpsc::{self, Sender};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+fn fails(_: &Job, _: Sender<String>) -> Result<(), String> {
+    Err("boom".to_string())
+}
+
+fn succeeds(_: &Job, _: Sender<String>) -> Result<(), String> {
+    Ok(())
+}
+
+#[test]
+fn metrics_endpoint_reports_dispatches_and_outcomes() {
+    let metrics = Arc::new(Metrics::new());
+    let queue = Arc::new(Mutex::new(QueueManager::new()));
+    let (tx, rx) = mpsc::channel();
+    let engine = TimePriorityEngine::new(Arc::clone(&queue), tx).with_metrics(Arc::clone(&metrics));
+    let mut worker = Worker::new().with_metrics(Arc::clone(&metrics));
+    worker.register("fails", fails).unwrap();
+    worker.register("succeeds", succeeds).unwrap();
+
+    let now = Utc::now().timestamp();
+    {
+        let mut q = queue.lock().unwrap();
+        q.push(Job::new(now, 1, "ok", "succeeds").unwrap());
+        q.push(Job::new(now, 1, "bad", "fails").unwrap());
+        q.push(Job::new(now + 3600, 1, "later", "succeeds").unwrap());
+    }
+    engine.start();
+    for _ in 0..2 {
+        let mut job = rx.recv_timeout(Duration::from_secs(1)).unwrap();
+        worker.run_job(&mut job);
+    }
+    engine.stop();
+
+    let server = ApiServer::start_with_metrics("127.0.0.1:0", queue, metrics).unwrap();
+    let mut stream = TcpStream::connect(server.local_addr()).unwrap();
+    write!(
+        stream,
+        "GET /metrics HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n"
+    )
+    .unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    server.stop();
+
+    assert!(response.starts_with("HTTP/1.1 200"), "{response}");
+    let lines: Vec<&str> = response.lines().collect();
+    for expected in [
+        "scheduler_jobs_dispatched_total 2",
+        "scheduler_jobs_succeeded_total 1",
+        "scheduler_jobs_failed_total 1",
+        "scheduler_queue_depth 1",
+        "scheduler_job_duration_seconds_count 2",
+        "scheduler_job_duration_seconds_bucket{le=\"+Inf\"} 2",
+    ] {
+        assert!(
+            lines.contains(&expected),
+            "missing '{expected}' in:\n{response}"
+        );
+    }
+}
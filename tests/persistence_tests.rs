@@ -0,0 +1,308 @@
+use scheduler::job::Job;
+use scheduler::persistence::{
+    DEFAULT_DEBOUNCE_WINDOW, DebouncedStore, InMemoryStore, JobStore, PersistenceManager,
+};
+use scheduler::queue::QueueManager;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use uuid::Uuid;
+
+fn now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs() as i64
+}
+
+fn job(exec_time: i64, desc: &str) -> Job {
+    Job::new(exec_time, 1, desc, "fn").unwrap()
+}
+
+fn temp_path() -> std::path::PathBuf {
+    std::env::temp_dir().join(format!("scheduler_jobs_{}.json", Uuid::new_v4()))
+}
+
+#[test]
+fn json_store_round_trips_jobs() {
+    let path = temp_path();
+    let store = PersistenceManager::new(&path);
+    let jobs = vec![job(now() + 10, "a"), job(now() + 20, "b")];
+
+    store.save(&jobs).unwrap();
+    let loaded = store.load().unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(loaded.len(), 2);
+    assert_eq!(loaded[0].id, jobs[0].id);
+    assert_eq!(loaded[1].description, "b");
+}
+
+#[test]
+fn json_store_missing_file_loads_empty() {
+    assert!(
+        PersistenceManager::new(temp_path())
+            .load()
+            .unwrap()
+            .is_empty()
+    );
+}
+
+#[test]
+fn queue_writes_mutations_to_its_store() {
+    let store = Arc::new(InMemoryStore::new());
+    let mut q = QueueManager::with_store(Box::new(Arc::clone(&store))).unwrap();
+
+    let j = job(now() + 10, "kept");
+    let id = j.id;
+    q.push(j);
+    q.push(job(now() + 20, "removed"));
+    assert_eq!(store.load().unwrap().len(), 2);
+
+    let removed = q.snapshot()[1].id;
+    q.remove(removed);
+    let saved = store.load().unwrap();
+    assert_eq!(saved.len(), 1);
+    assert_eq!(saved[0].id, id);
+}
+
+#[test]
+fn queue_restores_jobs_from_its_store() {
+    let saved = vec![job(now() + 10, "a"), job(now() + 20, "b")];
+    let store = InMemoryStore::with_jobs(saved);
+    let q = QueueManager::with_store(Box::new(store)).unwrap();
+
+    assert_eq!(q.len(), 2);
+    assert_eq!(q.peek().unwrap().description, "a");
+}
+
+#[test]
+fn replace_all_swaps_jobs_with_one_write() {
+    let store = Arc::new(InMemoryStore::new());
+    let mut q = QueueManager::with_store(Box::new(Arc::clone(&store))).unwrap();
+    for name in ["old 1", "old 2", "old 3"] {
+        q.push(job(now() + 10, name));
+    }
+    let writes_before = store.save_count();
+
+    q.replace_all(vec![job(now() + 30, "new 2"), job(now() + 20, "new 1")]);
+
+    assert_eq!(store.save_count() - writes_before, 1);
+    let names: Vec<String> = q.snapshot().into_iter().map(|j| j.description).collect();
+    assert_eq!(names, ["new 1", "new 2"]);
+    assert_eq!(store.load().unwrap().len(), 2);
+}
+
+#[test]
+fn paused_persistence_writes_once_on_resume() {
+    let store = Arc::new(InMemoryStore::new());
+    let mut q = QueueManager::with_store(Box::new(Arc::clone(&store))).unwrap();
+    q.push(job(now() + 10, "before"));
+    let writes_before = store.save_count();
+
+    q.set_persistence_enabled(false);
+    assert!(!q.persistence_enabled());
+    for name in ["a", "b", "c"] {
+        q.push(job(now() + 20, name));
+    }
+    q.pop();
+    assert_eq!(store.save_count(), writes_before);
+    assert_eq!(store.load().unwrap().len(), 1);
+
+    q.set_persistence_enabled(true);
+    assert_eq!(store.save_count() - writes_before, 1);
+    assert_eq!(store.load().unwrap().len(), 3);
+
+    // Enabling an already enabled queue doesn't write again
+    q.set_persistence_enabled(true);
+    assert_eq!(store.save_count() - writes_before, 1);
+}
+
+#[test]
+fn corrupt_store_is_refused_and_left_untouched() {
+    let path = temp_path();
+    std::fs::write(&path, "[{ not json").unwrap();
+
+    let err = PersistenceManager::new(&path).load().unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+    let result = QueueManager::with_store(Box::new(PersistenceManager::new(&path)));
+    let err = result.err().expect("corrupt store should be rejected");
+    assert!(err.contains("could not load saved jobs"), "{err}");
+
+    let contents = std::fs::read_to_string(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(contents, "[{ not json");
+}
+
+#[test]
+fn required_store_fails_on_unwritable_path() {
+    let path = std::env::temp_dir()
+        .join(format!("scheduler_missing_{}", Uuid::new_v4()))
+        .join("jobs.json");
+    let result = QueueManager::with_required_store(Box::new(PersistenceManager::new(&path)));
+    let err = result.err().expect("unwritable store should be rejected");
+    assert!(err.contains("persistence is required"), "{err}");
+
+    let path = temp_path();
+    let q = QueueManager::with_required_store(Box::new(PersistenceManager::new(&path))).unwrap();
+    assert!(q.is_empty());
+    assert!(path.exists());
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn json_store_reads_and_writes_rfc3339_times() {
+    use scheduler::time_format::TimeFormat;
+
+    let path = temp_path();
+    let exec = 1_893_456_000; // 2030-01-01T00:00:00Z
+    let mut original = job(now() + 10, "readable");
+    original.execution_time = exec;
+
+    let store = PersistenceManager::new(&path).with_time_format(TimeFormat::Rfc3339);
+    store.save(std::slice::from_ref(&original)).unwrap();
+    let raw = std::fs::read_to_string(&path).unwrap();
+    assert!(
+        raw.contains(r#""execution_time": "2030-01-01T00:00:00Z""#),
+        "{raw}"
+    );
+    assert_eq!(store.load().unwrap()[0].execution_time, exec);
+
+    // Integer files written before the option existed still load, with
+    // either store setting
+    let store = PersistenceManager::new(&path);
+    store.save(std::slice::from_ref(&original)).unwrap();
+    let raw = std::fs::read_to_string(&path).unwrap();
+    assert!(raw.contains(r#""execution_time": 1893456000"#), "{raw}");
+    let loaded = PersistenceManager::new(&path)
+        .with_time_format(TimeFormat::Rfc3339)
+        .load()
+        .unwrap();
+    assert_eq!(loaded[0].execution_time, exec);
+    assert_eq!(loaded[0].id, original.id);
+
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn debounced_store_coalesces_bursts_of_saves() {
+    let store = DebouncedStore::new(InMemoryStore::new(), DEFAULT_DEBOUNCE_WINDOW);
+    let jobs: Vec<Job> = (0..100).map(|i| job(now() + 10 + i, "burst")).collect();
+
+    for end in 1..=jobs.len() {
+        store.save(&jobs[..end]).unwrap();
+    }
+    store.flush();
+
+    let writes = store.inner().save_count();
+    assert!(writes < 10, "{writes} writes for 100 snapshots");
+    assert_eq!(store.inner().load().unwrap().len(), 100);
+}
+
+#[test]
+fn debounced_store_writes_pending_snapshot_on_drop() {
+    let inner = Arc::new(InMemoryStore::new());
+    let store = DebouncedStore::new(Arc::clone(&inner), Duration::from_secs(60));
+    store.save(&[job(now() + 10, "last")]).unwrap();
+    assert_eq!(inner.save_count(), 0);
+
+    drop(store);
+    assert_eq!(inner.save_count(), 1);
+    assert_eq!(inner.load().unwrap()[0].description, "last");
+}
+
+#[test]
+fn debounced_save_and_wait_confirms_the_write() {
+    let inner = Arc::new(InMemoryStore::new());
+    let store = DebouncedStore::new(Arc::clone(&inner), Duration::from_secs(60));
+
+    store
+        .save_and_wait(&[job(now() + 10, "confirmed")], Duration::from_secs(1))
+        .unwrap();
+    assert_eq!(inner.save_count(), 1);
+    assert_eq!(inner.load().unwrap()[0].description, "confirmed");
+
+    let unwritable = temp_path().join("queue.json");
+    let store = DebouncedStore::new(PersistenceManager::new(unwritable), Duration::from_secs(60));
+    assert!(store.save_and_wait(&[], Duration::from_secs(1)).is_err());
+}
+
+#[test]
+fn tags_round_trip_and_default_to_empty() {
+    let path = temp_path();
+    let store = PersistenceManager::new(&path);
+    store
+        .save(&[job(now() + 10, "tagged").with_tags(["nightly"])])
+        .unwrap();
+    let loaded = store.load().unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(loaded[0].tags, ["nightly"]);
+
+    let mut old = serde_json::to_value(job(now() + 10, "old")).unwrap();
+    old.as_object_mut().unwrap().remove("tags");
+    let old: Job = serde_json::from_value(old).unwrap();
+    assert!(old.tags.is_empty());
+}
+
+#[test]
+fn json_store_appends_history_without_touching_jobs() {
+    let path = temp_path();
+    let store = PersistenceManager::new(&path);
+    store.save(&[job(now() + 10, "queued")]).unwrap();
+
+    let mut done = Vec::new();
+    for desc in ["first", "second", "third"] {
+        let mut finished = job(now() + 10, desc);
+        finished.completed_at = Some(now());
+        store.append_history(&finished).unwrap();
+        done.push(finished);
+    }
+    let last_two = store.load_history(2);
+    let all = store.load_history(10);
+    let jobs = store.load().unwrap();
+    std::fs::remove_file(&path).unwrap();
+    std::fs::remove_file(store.history_path()).unwrap();
+
+    assert_eq!(
+        last_two.iter().map(|j| j.id).collect::<Vec<_>>(),
+        [done[1].id, done[2].id]
+    );
+    assert_eq!(all.len(), 3);
+    assert_eq!(all[0].completed_at, done[0].completed_at);
+    assert_eq!(jobs.len(), 1);
+    assert_eq!(jobs[0].description, "queued");
+    assert!(
+        PersistenceManager::new(temp_path())
+            .load_history(5)
+            .is_empty()
+    );
+}
+
+#[test]
+fn rfc3339_store_formats_completed_at() {
+    use scheduler::time_format::TimeFormat;
+
+    let path = temp_path();
+    let mut finished = job(now() + 10, "finished");
+    finished.completed_at = Some(1_893_456_000); // 2030-01-01T00:00:00Z
+
+    let store = PersistenceManager::new(&path).with_time_format(TimeFormat::Rfc3339);
+    store.save(std::slice::from_ref(&finished)).unwrap();
+    store.append_history(&finished).unwrap();
+    let raw = std::fs::read_to_string(&path).unwrap();
+    let history = std::fs::read_to_string(store.history_path()).unwrap();
+    let loaded = store.load().unwrap();
+    let loaded_history = store.load_history(1);
+    std::fs::remove_file(&path).unwrap();
+    std::fs::remove_file(store.history_path()).unwrap();
+
+    assert!(
+        raw.contains(r#""completed_at": "2030-01-01T00:00:00Z""#),
+        "{raw}"
+    );
+    assert!(
+        history.contains(r#""completed_at":"2030-01-01T00:00:00Z""#),
+        "{history}"
+    );
+    assert_eq!(loaded[0].completed_at, Some(1_893_456_000));
+    assert_eq!(loaded_history[0].completed_at, Some(1_893_456_000));
+}
@@ -1,4 +1,9 @@
-use scheduler::{job::Job, queue::QueueManager};
+use scheduler::{
+    job::{Job, Status},
+    persistence::InMemoryStore,
+    queue::QueueManager,
+};
+use std::sync::Arc;
 use std::time::{SystemTime, UNIX_EPOCH};
 use uuid::Uuid;
 
@@ -97,3 +102,723 @@ fn accepts_job_with_future_execution_time() {
     let result = Job::new(now() + 100, 5, "future job", "fn");
     assert!(result.is_ok());
 }
+
+#[test]
+fn import_specs_reports_valid_and_invalid_lines() {
+    let path = std::env::temp_dir().join(format!("scheduler_import_{}.jsonl", Uuid::new_v4()));
+    let contents = format!(
+        "{{\"execution_time\": {}, \"priority\": 3, \"description\": \"valid\", \"function\": \"fn\"}}\n\
+         {{\"execution_time\": 0, \"priority\": 3, \"description\": \"in the past\", \"function\": \"fn\"}}\n",
+        now() + 60
+    );
+    std::fs::write(&path, contents).unwrap();
+
+    let mut q = QueueManager::new();
+    let report = q.import_specs(&std::fs::read_to_string(&path).unwrap());
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(report.imported, 1);
+    assert_eq!(report.failed(), 1);
+    assert_eq!(report.errors[0].0, 2);
+    assert_eq!(q.len(), 1);
+    assert_eq!(q.peek().unwrap().description, "valid");
+}
+
+#[test]
+fn disabled_due_job_is_skipped_until_reenabled() {
+    let mut q = QueueManager::new();
+    let base = now();
+    let j = job(base + 10, 1, "paused");
+    let id = j.id;
+    q.push(j);
+    q.push(job(base + 10, 1, "active"));
+
+    assert!(q.set_enabled(id, false));
+    let ready = q.pop_ready(base + 10);
+    assert_eq!(ready.len(), 1);
+    assert_eq!(ready[0].description, "active");
+    assert_eq!(q.len(), 1);
+
+    assert!(q.set_enabled(id, true));
+    let ready = q.pop_ready(base + 10);
+    assert_eq!(ready.len(), 1);
+    assert_eq!(ready[0].id, id);
+    assert!(q.is_empty());
+}
+
+#[test]
+fn equal_time_and_priority_pop_in_insertion_order() {
+    let mut q = QueueManager::new();
+    let t = now() + 10;
+    for name in ["a", "b", "c", "d"] {
+        q.push(job(t, 1, name));
+    }
+    let order: Vec<String> = q.pop_ready(t).into_iter().map(|j| j.description).collect();
+    assert_eq!(order, ["a", "b", "c", "d"]);
+}
+
+#[test]
+fn restore_keeps_sequence_across_reload() {
+    let mut q = QueueManager::new();
+    let t = now() + 10;
+    for name in ["a", "b", "c"] {
+        q.push(job(t, 1, name));
+    }
+    q.pop();
+
+    let saved = serde_json::to_string(&q.snapshot()).unwrap();
+    let mut restored = QueueManager::restore(serde_json::from_str(&saved).unwrap());
+    restored.push(job(t, 1, "d"));
+
+    let jobs = restored.pop_ready(t);
+    let order: Vec<&str> = jobs.iter().map(|j| j.description.as_str()).collect();
+    assert_eq!(order, ["b", "c", "d"]);
+    assert!(jobs.windows(2).all(|w| w[0].seq < w[1].seq));
+}
+
+#[test]
+fn short_id_is_display_only() {
+    let mut q = QueueManager::new();
+    let j = job(now() + 10, 1, "target");
+    let id = j.id;
+    let short = j.short_id();
+    q.push(j);
+
+    assert_eq!(short.len(), 8);
+    assert!(id.to_string().starts_with(&short));
+    assert!(q.remove(id).is_some());
+}
+
+#[test]
+fn update_status_many_counts_matching_jobs() {
+    use scheduler::job::Status;
+
+    let mut q = QueueManager::new();
+    let jobs: Vec<Job> = (0..5).map(|i| job(now() + 10 + i, 1, "job")).collect();
+    let mut ids: Vec<Uuid> = jobs.iter().take(3).map(|j| j.id).collect();
+    for j in jobs {
+        q.push(j);
+    }
+    ids.push(Uuid::new_v4());
+
+    assert_eq!(q.update_status_many(&ids, Status::Running), 3);
+    let running = q
+        .snapshot()
+        .iter()
+        .filter(|j| j.status == Status::Running)
+        .count();
+    assert_eq!(running, 3);
+    assert_eq!(q.len(), 5);
+}
+
+#[test]
+fn update_status_many_skips_illegal_transitions() {
+    use scheduler::job::Status;
+
+    let mut q = QueueManager::new();
+    let pending = job(now() + 10, 1, "pending");
+    let running = job(now() + 10, 1, "running");
+    let ids = [pending.id, running.id];
+    q.push(pending);
+    q.push(running);
+    q.update_status(ids[1], Status::Running).unwrap();
+
+    // Pending -> Success is not a legal move; Running -> Success is
+    assert_eq!(q.update_status_many(&ids, Status::Success), 1);
+    assert_eq!(q.status_of(ids[0]), Some(Status::Pending));
+    assert_eq!(q.status_of(ids[1]), Some(Status::Success));
+}
+
+fn unknown_function_queue() -> (QueueManager, Uuid) {
+    let mut q = QueueManager::new();
+    let orphan = Job::new(now() + 10, 1, "orphan", "removed_fn").unwrap();
+    let id = orphan.id;
+    q.push(orphan);
+    q.push(job(now() + 10, 1, "known"));
+    (q, id)
+}
+
+#[test]
+fn check_functions_applies_each_policy() {
+    use scheduler::queue::UnknownFunctionPolicy;
+    use std::collections::HashSet;
+
+    let known = HashSet::from(["fn".to_string()]);
+
+    let (mut q, id) = unknown_function_queue();
+    let flagged = q.check_functions(&known, UnknownFunctionPolicy::KeepPending);
+    assert_eq!(flagged.len(), 1);
+    assert_eq!(flagged[0].id, id);
+    assert_eq!(q.pop_ready(now() + 10).len(), 2);
+
+    let (mut q, _) = unknown_function_queue();
+    q.check_functions(&known, UnknownFunctionPolicy::Disable);
+    assert_eq!(q.len(), 2);
+    let ready = q.pop_ready(now() + 10);
+    assert_eq!(ready.len(), 1);
+    assert_eq!(ready[0].description, "known");
+
+    let (mut q, id) = unknown_function_queue();
+    q.check_functions(&known, UnknownFunctionPolicy::DeadLetter);
+    assert_eq!(q.len(), 1);
+    assert_eq!(q.dead_letters().len(), 1);
+    assert_eq!(q.dead_letters()[0].id, id);
+}
+
+#[test]
+fn dependent_job_waits_for_prerequisite_success() {
+    let mut q = QueueManager::new();
+    let base = now();
+    let extract = job(base + 10, 1, "extract");
+    let extract_id = extract.id;
+    let transform = job(base, 9, "transform").with_dependencies([extract_id]);
+    q.push_checked(transform).unwrap();
+    q.push(extract);
+
+    // transform is due first but is held until extract has succeeded
+    assert!(q.pop_ready(base).is_empty());
+    let ready = q.pop_ready(base + 10);
+    assert_eq!(ready.len(), 1);
+    assert_eq!(ready[0].description, "extract");
+    assert_eq!(q.status_of(extract_id), Some(Status::Running));
+    assert!(q.pop_ready(base + 10).is_empty());
+
+    q.record_status(extract_id, Status::Success);
+    let ready = q.pop_ready(base + 10);
+    assert_eq!(ready.len(), 1);
+    assert_eq!(ready[0].description, "transform");
+}
+
+#[test]
+fn push_checked_rejects_dependency_cycles() {
+    let mut q = QueueManager::new();
+    let base = now() + 10;
+
+    let mut looped = job(base, 1, "self");
+    looped.depends_on = vec![looped.id];
+    assert!(q.push_checked(looped).is_err());
+
+    let a = job(base, 1, "a");
+    let b = job(base, 1, "b").with_dependencies([a.id]);
+    let a = a.with_dependencies([b.id]);
+    q.push_checked(b).unwrap();
+    let err = q.push_checked(a).unwrap_err();
+    assert!(err.contains("dependency cycle"), "{err}");
+    assert_eq!(q.len(), 1);
+}
+
+#[test]
+fn every_insertion_path_rejects_dependency_cycles() {
+    use scheduler::queue::PushError;
+
+    let mut q = QueueManager::new();
+    let base = now() + 10;
+    let a = job(base, 1, "a");
+    let b = job(base, 1, "b").with_dependencies([a.id]);
+    let (a_id, b_id) = (a.id, b.id);
+    q.push(a.clone());
+    q.push(b);
+
+    let looped = a.clone().with_dependencies([b_id]);
+    assert!(!q.push(looped.clone()));
+    assert_eq!(
+        q.try_push(looped.clone()),
+        Err(PushError::DependencyCycle(a_id))
+    );
+    let err = q.update_job(a_id, looped).unwrap_err();
+    assert!(err.contains("dependency cycle"), "{err}");
+    let queued = q.snapshot();
+    assert!(
+        queued
+            .iter()
+            .all(|j| j.id != a_id || j.depends_on.is_empty())
+    );
+}
+
+#[test]
+fn jobs_by_status_filters_mixed_queue() {
+    let mut q = QueueManager::new();
+    assert!(q.jobs_by_status(Status::Failed).is_empty());
+
+    let base = now() + 10;
+    let failed = job(base, 1, "failed");
+    let failed_id = failed.id;
+    q.push(failed);
+    q.push(job(base, 1, "pending a"));
+    q.push(job(base + 5, 1, "pending b"));
+    q.update_status(failed_id, Status::Running).unwrap();
+    q.update_status(failed_id, Status::Failed).unwrap();
+
+    let failed = q.jobs_by_status(Status::Failed);
+    assert_eq!(failed.len(), 1);
+    assert_eq!(failed[0].id, failed_id);
+    assert_eq!(q.jobs_by_status(Status::Pending).len(), 2);
+    assert!(q.jobs_by_status(Status::Running).is_empty());
+
+    let late = q.filter(|j| j.execution_time > base);
+    assert_eq!(late.len(), 1);
+    assert_eq!(late[0].description, "pending b");
+    assert_eq!(q.len(), 3);
+}
+
+#[test]
+fn in_place_updates_keep_dispatch_order() {
+    let mut q = QueueManager::new();
+    let base = now() + 10;
+    let ids: Vec<Uuid> = (0..50)
+        .map(|i| {
+            let j = job(base + (i % 7), (i % 3) as u8, &format!("job {i}"));
+            let id = j.id;
+            q.push(j);
+            id
+        })
+        .collect();
+    let before: Vec<Uuid> = q.snapshot().into_iter().map(|j| j.id).collect();
+
+    for id in &ids {
+        assert!(q.update_status(*id, Status::Running).is_ok());
+    }
+    for id in ids.iter().step_by(5) {
+        q.remove(*id);
+    }
+    assert_eq!(q.len(), 40);
+
+    let expected: Vec<Uuid> = before
+        .into_iter()
+        .filter(|id| !ids.iter().step_by(5).any(|r| r == id))
+        .collect();
+    let mut popped = Vec::new();
+    while let Some(job) = q.pop() {
+        assert_eq!(job.status, Status::Running);
+        popped.push(job.id);
+    }
+    assert_eq!(popped, expected);
+}
+
+#[test]
+fn pushing_a_queued_id_again_replaces_the_job() {
+    let mut q = QueueManager::new();
+    let base = now() + 10;
+    let mut first = job(base, 1, "first");
+    q.push(first.clone());
+    q.push(job(base + 5, 1, "second"));
+
+    first.execution_time = base + 10;
+    q.push(first);
+    assert_eq!(q.len(), 2);
+    assert_eq!(q.pop().unwrap().description, "second");
+    assert_eq!(q.pop().unwrap().description, "first");
+    assert!(q.pop().is_none());
+}
+
+#[test]
+fn pop_ready_returns_whole_same_instant_group_in_order() {
+    let mut q = QueueManager::new();
+    let t = now() + 10;
+    q.push(job(t + 1, 9, "later"));
+    for (prio, name) in [(1, "low a"), (5, "mid"), (1, "low b"), (9, "high")] {
+        q.push(job(t, prio, name));
+    }
+
+    let names: Vec<String> = q.pop_ready(t).into_iter().map(|j| j.description).collect();
+    assert_eq!(names, ["high", "mid", "low a", "low b"]);
+    assert_eq!(q.len(), 1);
+}
+
+#[test]
+fn readiness_is_checked_to_the_millisecond() {
+    use scheduler::clock::MockClock;
+
+    let clock = MockClock::new(1_900_000_000);
+    let t = 1_900_000_000_000;
+    let quarter = Job::new_at_millis_with_clock(t + 250, 1, "quarter", "fn", &clock).unwrap();
+    assert!(Job::new_at_millis_with_clock(t - 1, 1, "past", "fn", &clock).is_err());
+    let mut q = QueueManager::new();
+    q.push(quarter);
+
+    assert!(q.pop_ready(1_900_000_000).is_empty());
+    assert!(q.pop_ready_filtered_ms(t + 249, |_| true).is_empty());
+    let ready = q.pop_ready_filtered_ms(t + 250, |_| true);
+    assert_eq!(ready.len(), 1);
+    assert_eq!(ready[0].description, "quarter");
+}
+
+#[test]
+fn pop_ready_limited_resumes_a_split_group_first() {
+    let mut q = QueueManager::new();
+    let t = now() + 10;
+    for name in ["a", "b", "c", "d", "e"] {
+        q.push(job(t, 1, name));
+    }
+    q.push(job(t + 1, 9, "later but urgent"));
+
+    let names =
+        |jobs: Vec<Job>| -> Vec<String> { jobs.into_iter().map(|j| j.description).collect() };
+    assert_eq!(names(q.pop_ready_limited(t + 1, 2)), ["a", "b"]);
+    assert_eq!(names(q.pop_ready_limited(t + 1, 2)), ["c", "d"]);
+    assert_eq!(
+        names(q.pop_ready_limited(t + 1, 2)),
+        ["e", "later but urgent"]
+    );
+    assert!(q.pop_ready_limited(t + 1, 2).is_empty());
+    assert!(q.pop_ready_limited(t + 1, 0).is_empty());
+}
+
+#[test]
+fn shift_all_moves_pending_jobs_with_one_write() {
+    let store = Arc::new(InMemoryStore::new());
+    let mut q = QueueManager::with_store(Box::new(Arc::clone(&store))).unwrap();
+    let base = now() + 100;
+    q.push(job(base + 20, 1, "third"));
+    q.push(job(base, 1, "first"));
+    q.push(job(base + 10, 5, "second"));
+    let saves = store.save_count();
+
+    assert_eq!(q.shift_all(1800), Ok(3));
+    assert_eq!(store.save_count(), saves + 1);
+
+    let shifted: Vec<(i64, String)> = std::iter::from_fn(|| q.pop())
+        .map(|j| (j.execution_time, j.description))
+        .collect();
+    assert_eq!(
+        shifted,
+        [
+            (base + 1800, "first".to_string()),
+            (base + 1810, "second".to_string()),
+            (base + 1820, "third".to_string()),
+        ]
+    );
+}
+
+#[test]
+fn shift_all_rejects_moving_jobs_into_the_past() {
+    let mut q = QueueManager::new();
+    let due = now() + 60;
+    q.push(job(due, 1, "soon"));
+
+    assert!(q.shift_all(-3600).is_err());
+    assert_eq!(q.peek().unwrap().execution_time, due);
+}
+
+#[test]
+fn shift_all_moves_every_queued_job_by_the_queue_clock() {
+    use scheduler::clock::MockClock;
+    use scheduler::job::Status;
+
+    let mut q = QueueManager::new();
+    let base = now() + 100;
+    q.push(job(base, 1, "pending"));
+    let failed = job(base + 10, 1, "failed");
+    let failed_id = failed.id;
+    q.push(failed);
+    q.update_status(failed_id, Status::Running).unwrap();
+    q.update_status(failed_id, Status::Failed).unwrap();
+
+    assert_eq!(q.shift_all(60), Ok(2));
+    assert_eq!(q.peek().unwrap().execution_time, base + 60);
+    let failed = q.filter(|j| j.id == failed_id).remove(0);
+    assert_eq!(failed.execution_time, base + 70);
+
+    // By the mock clock the pending job is only 10s away
+    q.set_clock(Arc::new(MockClock::new(base + 50)));
+    assert!(q.shift_all(-30).unwrap_err().contains("into the past"));
+    assert!(q.shift_all(i64::MAX).unwrap_err().contains("out of range"));
+    assert_eq!(q.peek().unwrap().execution_time, base + 60);
+}
+
+#[test]
+fn shift_all_never_moves_overdue_jobs_further_into_the_past() {
+    use scheduler::clock::MockClock;
+
+    let mut q = QueueManager::new();
+    let base = now() + 100;
+    q.push(job(base, 1, "overdue"));
+    q.push(job(base + 30, 1, "later"));
+    // Both jobs were queued while in the future; the first is now 20s late
+    q.set_clock(Arc::new(MockClock::new(base + 20)));
+
+    assert_eq!(q.shift_all(-5), Ok(2));
+    let times: Vec<(String, i64)> = q
+        .snapshot()
+        .into_iter()
+        .map(|j| (j.description, j.execution_time))
+        .collect();
+    assert_eq!(
+        times,
+        [
+            ("overdue".to_string(), base + 20),
+            ("later".to_string(), base + 25)
+        ]
+    );
+
+    // Jobs still overdue after a forward shift land on now as well
+    q.set_clock(Arc::new(MockClock::new(base + 60)));
+    assert_eq!(q.shift_all(10), Ok(2));
+    assert!(q.snapshot().iter().all(|j| j.execution_time == base + 60));
+}
+
+#[test]
+fn update_job_edits_fields_but_keeps_identity() {
+    let mut q = QueueManager::new();
+    let original = job(now() + 50, 1, "nightly bakcup");
+    let id = original.id;
+    q.push(original);
+    q.push(job(now() + 40, 1, "other"));
+    q.update_status(id, Status::Running).unwrap();
+
+    let mut edited = job(now() + 30, 9, "nightly backup");
+    edited.retry_count = 7;
+    assert!(q.update_job(id, edited).is_ok());
+    assert!(
+        q.update_job(Uuid::new_v4(), job(now() + 30, 1, "missing"))
+            .is_err()
+    );
+
+    let first = q.pop().unwrap();
+    assert_eq!(first.id, id);
+    assert_eq!(first.description, "nightly backup");
+    assert_eq!(first.priority, 9);
+    assert_eq!(first.status, Status::Running);
+    assert_eq!(first.retry_count, 0);
+    assert_eq!(q.pop().unwrap().description, "other");
+    assert!(q.pop().is_none());
+}
+
+#[test]
+fn update_status_rejects_illegal_transitions() {
+    let mut q = QueueManager::new();
+    let j = job(now() + 10, 1, "job");
+    let id = j.id;
+    q.push(j);
+
+    assert!(q.update_status(id, Status::Success).is_err());
+    q.update_status(id, Status::Running).unwrap();
+    q.update_status(id, Status::Success).unwrap();
+    let err = q.update_status(id, Status::Running).unwrap_err();
+    assert!(err.contains("cannot move from Success to Running"), "{err}");
+    assert_eq!(q.status_of(id), Some(Status::Success));
+    assert!(q.update_status(Uuid::new_v4(), Status::Running).is_err());
+}
+
+#[test]
+fn remove_by_tag_handles_some_none_and_all_matching() {
+    let tagged =
+        |desc: &str, tags: &[&str]| job(now() + 10, 1, desc).with_tags(tags.iter().copied());
+    let mut q = QueueManager::new();
+    q.push(tagged("a", &["nightly", "customer-123"]));
+    q.push(tagged("b", &["nightly"]));
+    q.push(tagged("c", &["customer-123"]));
+    q.push(tagged("d", &[]));
+
+    let mut customer: Vec<String> = q
+        .jobs_with_tag("customer-123")
+        .into_iter()
+        .map(|j| j.description)
+        .collect();
+    customer.sort();
+    assert_eq!(customer, ["a", "c"]);
+
+    assert_eq!(q.remove_by_tag("missing"), 0);
+    assert_eq!(q.len(), 4);
+    assert_eq!(q.remove_by_tag("nightly"), 2);
+    assert_eq!(q.len(), 2);
+    assert_eq!(q.pop().unwrap().description, "c");
+
+    let mut q = QueueManager::new();
+    q.push(tagged("x", &["all"]));
+    q.push(tagged("y", &["all"]));
+    assert_eq!(q.remove_by_tag("all"), 2);
+    assert!(q.is_empty());
+    assert!(q.peek().is_none());
+}
+
+#[test]
+fn try_push_rejects_jobs_beyond_capacity() {
+    use scheduler::queue::PushError;
+
+    let mut q = QueueManager::with_capacity(3);
+    let jobs: Vec<Job> = (0..3).map(|i| job(now() + 10 + i, 1, "fill")).collect();
+    let first = jobs[0].clone();
+    for j in jobs {
+        q.try_push(j).unwrap();
+    }
+
+    assert_eq!(
+        q.try_push(job(now() + 20, 1, "overflow")),
+        Err(PushError::Full { capacity: 3 })
+    );
+    assert_eq!(q.len(), 3);
+    // Replacing a queued job doesn't grow the queue
+    assert!(q.try_push(first).is_ok());
+    q.push(job(now() + 30, 1, "unchecked"));
+    assert_eq!(q.len(), 4);
+}
+
+#[test]
+fn push_skips_duplicate_idempotency_key() {
+    let mut q = QueueManager::new();
+    let first = job(now() + 10, 1, "first").with_idempotency_key("order-42");
+    let first_id = first.id;
+    assert!(q.push(first));
+    assert_eq!(q.queued_with_key("order-42"), Some(first_id));
+
+    assert!(!q.push(job(now() + 5, 9, "resubmitted").with_idempotency_key("order-42")));
+    assert_eq!(q.len(), 1);
+    assert_eq!(q.peek().unwrap().id, first_id);
+
+    // Jobs without a key, or with another one, are unaffected
+    assert!(q.push(job(now() + 10, 1, "no key")));
+    assert!(q.push(job(now() + 10, 1, "other").with_idempotency_key("order-43")));
+    assert_eq!(q.len(), 3);
+}
+
+#[test]
+fn idempotency_key_is_reusable_once_its_job_leaves_the_queue() {
+    let mut q = QueueManager::new();
+    let first = job(now() + 5, 1, "first").with_idempotency_key("order-42");
+    let first_id = first.id;
+    q.push(first);
+    assert_eq!(q.pop().unwrap().id, first_id);
+    assert_eq!(q.queued_with_key("order-42"), None);
+
+    let again = job(now() + 10, 1, "again").with_idempotency_key("order-42");
+    let again_id = again.id;
+    assert!(q.push(again));
+    q.remove(again_id);
+    assert!(q.push(job(now() + 10, 1, "third").with_idempotency_key("order-42")));
+    assert_eq!(q.len(), 1);
+}
+
+#[test]
+fn push_rejects_unknown_functions_when_validation_is_enabled() {
+    use std::collections::HashSet;
+
+    let mut q = QueueManager::new();
+    let typo = Job::new(now() + 10, 1, "typo", "typo_fn").unwrap();
+    assert!(
+        q.validate_function(&typo).is_ok(),
+        "no validation by default"
+    );
+
+    let known = HashSet::from(["fn".to_string()]);
+    q.set_known_functions(Some(Arc::new(known)));
+    assert!(!q.push(typo.clone()));
+    let err = q.push_checked(typo.clone()).unwrap_err();
+    assert!(err.contains("typo_fn"), "{err}");
+    assert!(q.is_empty());
+
+    assert!(q.push(job(now() + 10, 1, "known")));
+    q.set_known_functions(None);
+    assert!(q.push(typo));
+    assert_eq!(q.len(), 2);
+}
+
+#[test]
+fn try_push_reports_why_a_job_was_refused() {
+    use scheduler::queue::PushError;
+    use std::collections::HashSet;
+
+    let mut q = QueueManager::new();
+    let first = job(now() + 10, 1, "first").with_idempotency_key("order-42");
+    let first_id = first.id;
+    q.try_push(first).unwrap();
+
+    let err = q
+        .try_push(job(now() + 10, 1, "again").with_idempotency_key("order-42"))
+        .unwrap_err();
+    assert_eq!(
+        err,
+        PushError::Duplicate {
+            key: "order-42".to_string(),
+            existing: first_id
+        }
+    );
+
+    q.set_known_functions(Some(Arc::new(HashSet::from(["fn".to_string()]))));
+    let typo = Job::new(now() + 10, 1, "typo", "typo_fn").unwrap();
+    assert_eq!(
+        q.try_push(typo),
+        Err(PushError::UnknownFunction("typo_fn".to_string()))
+    );
+    assert_eq!(q.len(), 1);
+}
+
+#[test]
+fn import_and_edits_are_checked_like_push() {
+    use std::collections::HashSet;
+
+    let mut q = QueueManager::new();
+    q.set_known_functions(Some(Arc::new(HashSet::from(["fn".to_string()]))));
+    let at = now() + 60;
+    let input = format!(
+        "{{\"execution_time\": {at}, \"priority\": 1, \"description\": \"ok\", \"function\": \"fn\", \"idempotency_key\": \"k\"}}\n\
+         {{\"execution_time\": {at}, \"priority\": 1, \"description\": \"typo\", \"function\": \"typo_fn\"}}\n\
+         {{\"execution_time\": {at}, \"priority\": 1, \"description\": \"again\", \"function\": \"fn\", \"idempotency_key\": \"k\"}}\n"
+    );
+    let report = q.import_specs(&input);
+    assert_eq!(report.imported, 1);
+    assert_eq!(report.errors.len(), 2);
+    assert_eq!(report.errors[0].0, 2);
+    assert!(
+        report.errors[0].1.contains("typo_fn"),
+        "{:?}",
+        report.errors
+    );
+    assert_eq!(report.errors[1].0, 3);
+    assert!(
+        report.errors[1].1.contains("idempotency key"),
+        "{:?}",
+        report.errors
+    );
+
+    let id = q.peek().unwrap().id;
+    let typo = Job::new(at, 1, "edited", "typo_fn").unwrap();
+    assert!(q.update_job(id, typo).unwrap_err().contains("typo_fn"));
+    assert_eq!(q.peek().unwrap().function, "fn");
+}
+
+#[test]
+fn finished_outcomes_are_trimmed_unless_a_queued_job_depends_on_them() {
+    use scheduler::job::Status;
+
+    let mut q = QueueManager::new();
+    q.set_outcome_limit(2);
+    let needed = Uuid::new_v4();
+    let mut dependent = job(now() + 10, 1, "dependent");
+    dependent.depends_on = vec![needed];
+    q.push(dependent);
+
+    let running = Uuid::new_v4();
+    q.record_status(running, Status::Running);
+    q.record_status(needed, Status::Success);
+    let finished: Vec<Uuid> = (0..5).map(|_| Uuid::new_v4()).collect();
+    for &id in &finished {
+        q.record_status(id, Status::Success);
+    }
+
+    assert_eq!(q.status_of(needed), Some(Status::Success));
+    assert_eq!(q.status_of(running), Some(Status::Running));
+    let remembered = finished
+        .iter()
+        .filter(|&&id| q.status_of(id).is_some())
+        .count();
+    assert!(remembered <= 1, "{remembered} finished outcomes kept");
+}
+
+#[test]
+fn running_outcomes_past_the_limit_are_forgotten_oldest_first() {
+    use scheduler::job::Status;
+
+    let mut q = QueueManager::new();
+    q.set_outcome_limit(2);
+    // Jobs whose result never comes back would otherwise be kept forever
+    let running: Vec<Uuid> = (0..4).map(|_| Uuid::new_v4()).collect();
+    for &id in &running {
+        q.record_status(id, Status::Running);
+    }
+
+    assert_eq!(q.status_of(running[0]), None);
+    assert_eq!(q.status_of(running[1]), None);
+    assert_eq!(q.status_of(running[2]), Some(Status::Running));
+    assert_eq!(q.status_of(running[3]), Some(Status::Running));
+}
@@ -0,0 +1,158 @@
+#![cfg(feature = "sqlite")]
+
+use scheduler::job::{Job, Status};
+use scheduler::persistence::JobStore;
+use scheduler::sqlite_store::SqliteStore;
+use std::time::{SystemTime, UNIX_EPOCH};
+use uuid::Uuid;
+
+fn now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs() as i64
+}
+
+fn job(exec_time: i64, desc: &str) -> Job {
+    Job::new(exec_time, 1, desc, "fn").unwrap()
+}
+
+#[test]
+fn new_database_creates_schema_and_loads_empty() {
+    let path = std::env::temp_dir().join(format!("scheduler_{}.db", Uuid::new_v4()));
+    let store = SqliteStore::open(&path).unwrap();
+    assert!(store.load().unwrap().is_empty());
+    drop(store);
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn jobs_round_trip_through_sqlite() {
+    let path = std::env::temp_dir().join(format!("scheduler_{}.db", Uuid::new_v4()));
+    let mut failed = job(now() + 10, "failed one")
+        .with_payload(serde_json::json!({ "to": "ops@example.com" }))
+        .with_schedule("0 3 * * *")
+        .unwrap();
+    failed.status = Status::Failed;
+    let jobs = vec![failed, job(now() + 20, "pending one")];
+
+    SqliteStore::open(&path).unwrap().save(&jobs).unwrap();
+    let loaded = SqliteStore::open(&path).unwrap().load().unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(loaded.len(), 2);
+    let restored = loaded.iter().find(|j| j.id == jobs[0].id).unwrap();
+    assert_eq!(restored.status, Status::Failed);
+    assert_eq!(restored.description, "failed one");
+    assert_eq!(restored.payload["to"], "ops@example.com");
+    assert_eq!(restored.schedule.as_deref(), Some("0 3 * * *"));
+}
+
+#[test]
+fn retry_counts_are_stored_in_their_own_columns() {
+    let path = std::env::temp_dir().join(format!("scheduler_{}.db", Uuid::new_v4()));
+    let mut retried = job(now() + 10, "retried").with_max_retries(5);
+    retried.retry_count = 2;
+    SqliteStore::open(&path)
+        .unwrap()
+        .save(std::slice::from_ref(&retried))
+        .unwrap();
+
+    let conn = rusqlite::Connection::open(&path).unwrap();
+    let (max_retries, retry_count, extra): (u32, u32, String) = conn
+        .query_row(
+            "SELECT max_retries, retry_count, extra FROM jobs",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        )
+        .unwrap();
+    drop(conn);
+    let loaded = SqliteStore::open(&path).unwrap().load().unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!((max_retries, retry_count), (5, 2));
+    assert!(!extra.contains("retry_count"), "{extra}");
+    assert_eq!(loaded[0].max_retries, 5);
+    assert_eq!(loaded[0].retry_count, 2);
+}
+
+#[test]
+fn databases_without_retry_columns_are_migrated() {
+    let path = std::env::temp_dir().join(format!("scheduler_{}.db", Uuid::new_v4()));
+    let old = job(now() + 10, "from before the columns");
+    let conn = rusqlite::Connection::open(&path).unwrap();
+    conn.execute_batch(
+        "CREATE TABLE jobs (
+            id TEXT PRIMARY KEY, execution_time INTEGER NOT NULL, priority INTEGER NOT NULL,
+            description TEXT NOT NULL, function TEXT NOT NULL, status TEXT NOT NULL,
+            extra TEXT NOT NULL
+        );",
+    )
+    .unwrap();
+    conn.execute(
+        "INSERT INTO jobs VALUES (?1, ?2, 1, 'from before the columns', 'fn', 'Pending', ?3)",
+        rusqlite::params![
+            old.id.to_string(),
+            old.execution_time,
+            r#"{"max_retries": 4, "retry_count": 1}"#,
+        ],
+    )
+    .unwrap();
+    drop(conn);
+
+    let loaded = SqliteStore::open(&path).unwrap().load().unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(loaded.len(), 1);
+    assert_eq!(loaded[0].id, old.id);
+    assert_eq!(loaded[0].max_retries, 4);
+    assert_eq!(loaded[0].retry_count, 1);
+}
+
+#[test]
+fn save_only_writes_changed_rows() {
+    let store = SqliteStore::open_in_memory().unwrap();
+    let mut jobs: Vec<Job> = (0..5).map(|i| job(now() + 10 + i, "job")).collect();
+    store.save(&jobs).unwrap();
+    let after_first = store.total_changes();
+    assert_eq!(after_first, 5);
+
+    jobs[2].status = Status::Running;
+    let gone = jobs.pop().unwrap();
+    store.save(&jobs).unwrap();
+    assert_eq!(
+        store.total_changes() - after_first,
+        2,
+        "one update, one delete"
+    );
+
+    let loaded = store.load().unwrap();
+    assert_eq!(loaded.len(), 4);
+    assert!(loaded.iter().all(|j| j.id != gone.id));
+}
+
+#[test]
+fn history_is_appended_and_loaded_newest_last() {
+    let store = SqliteStore::open_in_memory().unwrap();
+    assert!(store.load_history(10).is_empty());
+
+    let mut ids = Vec::new();
+    for desc in ["first", "second", "third"] {
+        let mut finished = job(now() + 10, desc);
+        finished.status = Status::Success;
+        finished.completed_at = Some(now());
+        store.append_history(&finished).unwrap();
+        ids.push(finished.id);
+    }
+    store.save(&[job(now() + 10, "queued")]).unwrap();
+
+    let last_two = store.load_history(2);
+    assert_eq!(
+        last_two.iter().map(|j| j.id).collect::<Vec<_>>(),
+        [ids[1], ids[2]]
+    );
+    assert_eq!(last_two[1].status, Status::Success);
+    assert!(last_two[1].completed_at.is_some());
+    assert_eq!(store.load_history(10).len(), 3);
+    assert_eq!(store.load().unwrap().len(), 1);
+}
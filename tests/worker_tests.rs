@@ -1,9 +1,13 @@
 use scheduler::{
-    job::{Job, Status},
-    worker::Worker,
+    job::{Isolation, Job, JobSpec, MAX_DESCRIPTION_LEN, MAX_PAYLOAD_BYTES, SpecError, Status},
+    persistence::{InMemoryStore, JobStore},
+    queue::QueueManager,
+    worker::{JobEvent, Outcome, Worker, supervise},
 };
-use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::mpsc;
+use serde_json::{Value, json};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Sender};
+use std::sync::{Arc, Mutex};
 use std::thread;
 use std::time::Duration;
 
@@ -13,8 +17,40 @@ mod tests {
     // We use a static atomic to track if the function was called
     static WAS_CALLED: AtomicBool = AtomicBool::new(false);
 
-    fn test_task() {
+    fn test_task(_: &Job, _: Sender<String>) -> Result<(), String> {
         WAS_CALLED.store(true, Ordering::SeqCst);
+        Ok(())
+    }
+
+    /// Builds a job that is already due (execution_time = 0), bypassing `Job::new`'s validation.
+    fn job(function: &str, description: &str, priority: u8) -> Job {
+        Job {
+            id: uuid::Uuid::new_v4(),
+            function: function.to_string(),
+            description: description.to_string(),
+            priority,
+            execution_time: 0,
+            status: Status::Pending,
+            enabled: true,
+            seq: 0,
+            isolation: Isolation::Pooled,
+            estimated_duration_ms: None,
+            schedule: None,
+            payload: Value::Null,
+            max_retries: 0,
+            retry_count: 0,
+            retry_policy: Default::default(),
+            timeout_secs: None,
+            depends_on: Vec::new(),
+            condition: None,
+            execution_ms: 0,
+            tags: Vec::new(),
+            idempotency_key: None,
+            group: None,
+            completed_at: None,
+            series_id: None,
+            cancel: Default::default(),
+        }
     }
 
     #[test]
@@ -22,21 +58,14 @@ mod tests {
         let mut worker = Worker::new();
 
         // 1. Register our test function
-        worker.register("test_func", test_task);
+        worker.register("test_func", test_task).unwrap();
 
         // 2. Create a job that is ready to run (execution_time = 0)
-        let job = Job {
-            id: uuid::Uuid::new_v4(),
-            function: "test_func".to_string(),
-            description: "A test job for the registry".to_string(),
-            priority: 1,
-            execution_time: 0,
-            status: Status::Pending,
-        };
+        let mut job = job("test_func", "A test job for the registry", 1);
 
         // 3. Reset the flag and run the job
         WAS_CALLED.store(false, Ordering::SeqCst);
-        worker.run_job(&job);
+        worker.run_job(&mut job);
 
         // 4. Assert the function was triggered
         assert!(
@@ -49,23 +78,16 @@ mod tests {
     fn test_unknown_function_graceful_failure() {
         let worker = Worker::new(); // No functions registered
 
-        let job = Job {
-            id: uuid::Uuid::new_v4(),
-            function: "missing_func".to_string(),
-            description: "A test job for the registry".to_string(),
-            priority: 2,
-            execution_time: 0,
-            status: Status::Pending,
-        };
+        let mut job = job("missing_func", "A test job for the registry", 2);
 
         // Should not panic, just log an error
-        worker.run_job(&job);
+        worker.run_job(&mut job);
     }
 
     #[test]
     fn test_worker_start_channel() {
         let mut worker = Worker::new();
-        worker.register("test_func", test_task);
+        worker.register("test_func", test_task).unwrap();
 
         let (tx, rx) = mpsc::channel();
         WAS_CALLED.store(false, Ordering::SeqCst);
@@ -75,14 +97,7 @@ mod tests {
             worker.start(rx);
         });
 
-        let job = Job {
-            id: uuid::Uuid::new_v4(),
-            function: "test_func".to_string(),
-            description: "Test channel job".to_string(),
-            priority: 1,
-            execution_time: 0,
-            status: Status::Pending,
-        };
+        let job = job("test_func", "Test channel job", 1);
 
         tx.send(job).unwrap();
 
@@ -95,4 +110,513 @@ mod tests {
             "The registered function should have been executed via channel"
         );
     }
+
+    /// Reports that it has started, then holds its thread until cancelled.
+    fn holds_thread(job: &Job, log: Sender<String>) -> Result<(), String> {
+        let _ = log.send("holding".to_string());
+        while !job.is_cancelled() {
+            thread::yield_now();
+        }
+        Ok(())
+    }
+
+    fn reports_done(_: &Job, log: Sender<String>) -> Result<(), String> {
+        let _ = log.send("done".to_string());
+        Ok(())
+    }
+
+    #[test]
+    fn test_dedicated_job_runs_alongside_pooled_jobs() {
+        let (log_tx, log_rx) = mpsc::channel();
+        let mut worker = Worker::new().with_threads(2).with_log_sink(log_tx);
+        worker.register("holds_thread", holds_thread).unwrap();
+        worker.register("reports_done", reports_done).unwrap();
+
+        let (tx, rx) = mpsc::channel();
+        let pool = {
+            let worker = worker.clone();
+            thread::spawn(move || worker.start(rx))
+        };
+        let blockers: Vec<Job> = (0..worker.threads())
+            .map(|_| job("holds_thread", "Pooled job", 1))
+            .collect();
+        for blocker in &blockers {
+            tx.send(blocker.clone()).unwrap();
+        }
+        for _ in &blockers {
+            assert_eq!(
+                log_rx.recv_timeout(Duration::from_secs(5)).unwrap(),
+                "holding"
+            );
+        }
+
+        // Every pool thread is taken, so the job can only finish on its own
+        let mut dedicated = job("reports_done", "Dedicated job", 1);
+        dedicated.isolation = Isolation::Dedicated;
+        worker.handle(dedicated);
+        assert_eq!(log_rx.recv_timeout(Duration::from_secs(5)).unwrap(), "done");
+
+        for blocker in &blockers {
+            assert!(worker.cancel(blocker.id), "pooled job is still running");
+        }
+        drop(tx);
+        pool.join().unwrap();
+        worker.shutdown();
+    }
+
+    fn spec(execution_time: i64, function: &str) -> JobSpec {
+        JobSpec {
+            execution_time,
+            priority: 1,
+            description: "spec".to_string(),
+            function: function.to_string(),
+            payload: Value::Null,
+            max_retries: 0,
+            idempotency_key: None,
+        }
+    }
+
+    #[test]
+    fn test_validate_spec() {
+        let mut worker = Worker::new();
+        worker.register("test_func", test_task).unwrap();
+        let future = chrono::Utc::now().timestamp() + 60;
+
+        assert!(worker.validate_spec(&spec(future, "test_func")).is_ok());
+
+        assert_eq!(
+            worker.validate_spec(&spec(0, "test_func")),
+            Err(SpecError::ExecutionTimeInPast(0))
+        );
+        assert_eq!(
+            worker.validate_spec(&spec(future, "")),
+            Err(SpecError::EmptyFunction)
+        );
+        let err = worker
+            .validate_spec(&spec(future, "missing_func"))
+            .unwrap_err();
+        assert_eq!(err, SpecError::UnknownFunction("missing_func".to_string()));
+        assert_eq!(err.to_string(), "no function registered for 'missing_func'");
+    }
+
+    #[test]
+    fn test_validate_spec_size_limits() {
+        let mut worker = Worker::new();
+        worker.register("test_func", test_task).unwrap();
+        let future = chrono::Utc::now().timestamp() + 60;
+
+        let mut long = spec(future, "test_func");
+        long.description = "x".repeat(MAX_DESCRIPTION_LEN);
+        assert!(worker.validate_spec(&long).is_ok());
+        long.description.push('x');
+        assert_eq!(
+            worker.validate_spec(&long),
+            Err(SpecError::DescriptionTooLong {
+                len: MAX_DESCRIPTION_LEN + 1,
+                max: MAX_DESCRIPTION_LEN
+            })
+        );
+
+        let mut large = spec(future, "test_func");
+        large.payload = json!("x".repeat(MAX_PAYLOAD_BYTES));
+        assert!(matches!(
+            worker.validate_spec(&large),
+            Err(SpecError::PayloadTooLarge { len, max: MAX_PAYLOAD_BYTES }) if len == MAX_PAYLOAD_BYTES + 2
+        ));
+        // The import path applies the same limits
+        assert!(large.into_job().unwrap_err().contains("payload is"));
+    }
+
+    fn panicking_task(_: &Job, _: Sender<String>) -> Result<(), String> {
+        panic!("boom");
+    }
+
+    #[test]
+    fn test_run_now_outcomes() {
+        let mut worker = Worker::new();
+        worker.register("test_func", test_task).unwrap();
+        worker.register("panics", panicking_task).unwrap();
+
+        assert_eq!(
+            worker.run_now("test_func", &Value::Null, None),
+            Outcome::Success
+        );
+        assert_eq!(
+            worker.run_now("missing_func", &Value::Null, None),
+            Outcome::NotFound
+        );
+        assert_eq!(
+            worker.run_now("panics", &Value::Null, None),
+            Outcome::Panicked("boom".to_string())
+        );
+    }
+
+    #[test]
+    fn test_supervisor_restarts_dead_worker() {
+        let (tx, rx) = mpsc::channel();
+        let (done_tx, done_rx) = mpsc::channel();
+
+        let supervisor = supervise(rx, move |job| {
+            if job.function == "die" {
+                panic!("worker killed");
+            }
+            done_tx.send(job.description).unwrap();
+        });
+
+        tx.send(job("die", "Kills the worker thread", 1)).unwrap();
+        tx.send(job("test_func", "Picked up by replacement", 1))
+            .unwrap();
+
+        let processed = done_rx.recv_timeout(Duration::from_secs(1)).unwrap();
+        assert_eq!(processed, "Picked up by replacement");
+
+        drop(tx);
+        supervisor.join().unwrap();
+    }
+
+    fn greet(job: &Job, log_tx: Sender<String>) -> Result<(), String> {
+        let name = job.payload["name"].as_str().unwrap_or("nobody");
+        log_tx.send(format!("hello {name}")).unwrap();
+        Ok(())
+    }
+
+    #[test]
+    fn test_payload_reaches_the_function() {
+        let (log_tx, log_rx) = mpsc::channel();
+        let mut worker = Worker::new().with_log_sink(log_tx);
+        worker.register("greet", greet).unwrap();
+
+        let mut job = job("greet", "Payload job", 1);
+        job.payload = json!({ "name": "ada" });
+        worker.run_job(&mut job);
+
+        assert_eq!(log_rx.try_recv().unwrap(), "hello ada");
+    }
+
+    fn slow_task(_: &Job, log_tx: Sender<String>) -> Result<(), String> {
+        let _ = log_tx.send("start".to_string());
+        thread::sleep(Duration::from_millis(300));
+        let _ = log_tx.send("end".to_string());
+        Ok(())
+    }
+
+    #[test]
+    fn test_thread_pool_runs_jobs_concurrently() {
+        let (log_tx, log_rx) = mpsc::channel();
+        let mut worker = Worker::new().with_threads(2).with_log_sink(log_tx);
+        worker.register("slow", slow_task).unwrap();
+
+        let (tx, rx) = mpsc::channel();
+        let started = std::time::Instant::now();
+        let pool = thread::spawn(move || worker.start(rx));
+        tx.send(job("slow", "First slow job", 1)).unwrap();
+        tx.send(job("slow", "Second slow job", 1)).unwrap();
+        drop(tx);
+        pool.join().unwrap();
+
+        // Sequential execution would take at least 600ms
+        assert!(started.elapsed() < Duration::from_millis(550));
+        let lines: Vec<String> = log_rx.try_iter().collect();
+        assert_eq!(lines, ["start", "start", "end", "end"]);
+    }
+
+    static FLAKY_CALLS: AtomicUsize = AtomicUsize::new(0);
+
+    fn flaky_task(_: &Job, _: Sender<String>) -> Result<(), String> {
+        match FLAKY_CALLS.fetch_add(1, Ordering::SeqCst) {
+            0 | 1 => Err("temporarily unavailable".to_string()),
+            _ => Ok(()),
+        }
+    }
+
+    #[test]
+    fn test_failed_job_is_retried_until_it_succeeds() {
+        let queue = Arc::new(Mutex::new(QueueManager::new()));
+        let mut worker = Worker::new().with_queue(Arc::clone(&queue));
+        worker.register("flaky", flaky_task).unwrap();
+
+        let mut flaky = job("flaky", "Fails twice", 1);
+        flaky.max_retries = 3;
+        queue.lock().unwrap().push(flaky);
+
+        let mut last = None;
+        loop {
+            let next = queue.lock().unwrap().pop();
+            let Some(mut job) = next else { break };
+            worker.run_job(&mut job);
+            last = Some(job);
+        }
+
+        let last = last.unwrap();
+        assert_eq!(FLAKY_CALLS.load(Ordering::SeqCst), 3);
+        assert_eq!(last.retry_count, 2);
+        assert_eq!(last.status, Status::Success);
+    }
+
+    #[test]
+    fn test_failed_job_without_retries_stays_failed() {
+        fn always_fails(_: &Job, _: Sender<String>) -> Result<(), String> {
+            Err("nope".to_string())
+        }
+        let queue = Arc::new(Mutex::new(QueueManager::new()));
+        let mut worker = Worker::new().with_queue(Arc::clone(&queue));
+        worker.register("fails", always_fails).unwrap();
+
+        let mut job = job("fails", "No retries", 1);
+        worker.run_job(&mut job);
+
+        assert_eq!(job.status, Status::Failed);
+        assert!(queue.lock().unwrap().is_empty());
+        assert_eq!(
+            worker.run_now("fails", &Value::Null, None),
+            Outcome::Failed("nope".to_string())
+        );
+    }
+
+    #[test]
+    fn test_refused_retry_is_recorded_as_failed() {
+        fn always_fails(_: &Job, _: Sender<String>) -> Result<(), String> {
+            Err("nope".to_string())
+        }
+        let store = Arc::new(InMemoryStore::new());
+        let queue = Arc::new(Mutex::new(
+            QueueManager::with_store(Box::new(Arc::clone(&store))).unwrap(),
+        ));
+        let mut worker = Worker::new().with_queue(Arc::clone(&queue));
+        worker.register("fails", always_fails).unwrap();
+
+        // A resubmission took the key while the job was running, so the
+        // queue refuses to take the retry back
+        let mut refused = job("fails", "Refused retry", 1).with_idempotency_key("nightly");
+        refused.max_retries = 3;
+        let resubmitted = job("fails", "Resubmitted", 1).with_idempotency_key("nightly");
+        queue.lock().unwrap().push(resubmitted);
+        worker.run_job(&mut refused);
+
+        assert_eq!(refused.status, Status::Failed);
+        assert!(refused.completed_at.is_some());
+        let queue = queue.lock().unwrap();
+        assert_eq!(queue.len(), 1);
+        assert_eq!(queue.status_of(refused.id), Some(Status::Failed));
+        let history = store.load_history(10);
+        assert_eq!(history.len(), 1);
+        assert_eq!(history[0].id, refused.id);
+    }
+
+    fn hanging_task(_: &Job, _: Sender<String>) -> Result<(), String> {
+        thread::sleep(Duration::from_secs(5));
+        Ok(())
+    }
+
+    #[test]
+    fn test_job_times_out_and_worker_moves_on() {
+        let mut worker = Worker::new();
+        worker.register("hangs", hanging_task).unwrap();
+
+        let mut job = job("hangs", "Stale network mount", 1);
+        job.timeout_secs = Some(1);
+
+        let started = std::time::Instant::now();
+        worker.run_job(&mut job);
+
+        assert!(started.elapsed() < Duration::from_millis(1200));
+        assert_eq!(job.status, Status::Failed);
+
+        let started = std::time::Instant::now();
+        assert_eq!(
+            worker.run_now("hangs", &Value::Null, Some(1)),
+            Outcome::TimedOut(1)
+        );
+        assert!(started.elapsed() < Duration::from_millis(1200));
+    }
+
+    #[test]
+    fn test_start_finishes_running_job_after_channel_closes() {
+        let (log_tx, log_rx) = mpsc::channel();
+        let mut worker = Worker::new().with_log_sink(log_tx);
+        worker.register("slow", slow_task).unwrap();
+
+        let (tx, rx) = mpsc::channel();
+        let runner = worker.clone();
+        let handle = thread::spawn(move || runner.start(rx));
+        tx.send(job("slow", "In flight at shutdown", 1)).unwrap();
+        assert_eq!(
+            log_rx.recv_timeout(Duration::from_secs(1)).unwrap(),
+            "start"
+        );
+        drop(tx);
+
+        handle.join().unwrap();
+        assert_eq!(log_rx.try_recv().unwrap(), "end");
+    }
+
+    #[test]
+    fn test_shutdown_waits_for_dedicated_job() {
+        let (log_tx, log_rx) = mpsc::channel();
+        let mut worker = Worker::new().with_log_sink(log_tx);
+        worker.register("slow", slow_task).unwrap();
+
+        let mut dedicated = job("slow", "Runs on its own thread", 1);
+        dedicated.isolation = Isolation::Dedicated;
+        worker.handle(dedicated);
+
+        worker.shutdown();
+        let lines: Vec<String> = log_rx.try_iter().collect();
+        assert_eq!(lines, ["start", "end"]);
+    }
+
+    static FAIL_ONCE_CALLS: AtomicUsize = AtomicUsize::new(0);
+
+    fn fails_once(_: &Job, _: Sender<String>) -> Result<(), String> {
+        match FAIL_ONCE_CALLS.fetch_add(1, Ordering::SeqCst) {
+            0 => Err("connection reset".to_string()),
+            _ => Ok(()),
+        }
+    }
+
+    #[test]
+    fn test_event_sink_reports_failure_retry_and_success() {
+        let queue = Arc::new(Mutex::new(QueueManager::new()));
+        let (events_tx, events_rx) = mpsc::channel();
+        let mut worker = Worker::new()
+            .with_queue(Arc::clone(&queue))
+            .with_event_sink(events_tx);
+        worker.register("fails_once", fails_once).unwrap();
+
+        let mut job = job("fails_once", "Monitored", 1);
+        job.max_retries = 1;
+        let id = job.id;
+        worker.run_job(&mut job);
+        let mut retry = queue.lock().unwrap().pop().unwrap();
+        worker.run_job(&mut retry);
+
+        let events: Vec<JobEvent> = events_rx.try_iter().collect();
+        assert_eq!(
+            events,
+            [
+                JobEvent::Started(id),
+                JobEvent::Failed {
+                    id,
+                    error: "connection reset".to_string()
+                },
+                JobEvent::Retrying { id, attempt: 1 },
+                JobEvent::Started(id),
+                JobEvent::Succeeded(id),
+            ]
+        );
+    }
+
+    fn runs_until_cancelled(job: &Job, _: Sender<String>) -> Result<(), String> {
+        // Bounded so a broken cancel fails the test rather than hanging it
+        for _ in 0..500 {
+            if job.is_cancelled() {
+                return Ok(());
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        Err("never cancelled".to_string())
+    }
+
+    #[test]
+    fn test_cancel_stops_a_running_job() {
+        let queue = Arc::new(Mutex::new(QueueManager::new()));
+        let (events_tx, events_rx) = mpsc::channel();
+        let mut worker = Worker::new()
+            .with_queue(Arc::clone(&queue))
+            .with_event_sink(events_tx);
+        worker
+            .register("runs_until_cancelled", runs_until_cancelled)
+            .unwrap();
+
+        let mut job = job("runs_until_cancelled", "Long-running", 1);
+        job.max_retries = 3;
+        let id = job.id;
+        assert!(!worker.cancel(id), "not running yet");
+
+        let runner = worker.clone();
+        let handle = thread::spawn(move || {
+            runner.run_job(&mut job);
+            job
+        });
+        let started = events_rx.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!(started, JobEvent::Started(id));
+        assert!(worker.cancel(id));
+
+        let job = handle.join().unwrap();
+        assert_eq!(job.status, Status::Cancelled);
+        assert_eq!(
+            events_rx.recv_timeout(Duration::from_secs(5)).unwrap(),
+            JobEvent::Cancelled(id)
+        );
+        assert!(
+            queue.lock().unwrap().is_empty(),
+            "cancelled jobs aren't retried"
+        );
+        assert!(!worker.cancel(id), "no longer running");
+    }
+
+    #[test]
+    fn test_duplicate_registration_is_rejected() {
+        let mut worker = Worker::new();
+        worker.register("test_func", test_task).unwrap();
+
+        let err = worker.register("test_func", panicking_task).unwrap_err();
+        assert!(err.contains("test_func"), "{err}");
+        assert_eq!(
+            worker.run_now("test_func", &Value::Null, None),
+            Outcome::Success,
+            "the first registration is kept"
+        );
+
+        worker.register_or_replace("test_func", panicking_task);
+        assert!(matches!(
+            worker.run_now("test_func", &Value::Null, None),
+            Outcome::Panicked(_)
+        ));
+        assert_eq!(worker.registered_names(), ["test_func"]);
+    }
+
+    #[test]
+    fn test_registered_names_are_sorted_and_shared_by_clones() {
+        let mut worker = Worker::new();
+        assert!(worker.registered_names().is_empty());
+        worker.register("slow", slow_task).unwrap();
+        worker.register("greet", greet).unwrap();
+
+        let mut clone = worker.clone();
+        clone.register("heavy", reports_done).unwrap();
+        assert_eq!(worker.registered_names(), ["greet", "heavy", "slow"]);
+    }
+
+    #[test]
+    fn test_finished_jobs_are_recorded_in_history() {
+        fn always_fails(_: &Job, _: Sender<String>) -> Result<(), String> {
+            Err("disk full".to_string())
+        }
+
+        let store = Arc::new(InMemoryStore::new());
+        let queue = Arc::new(Mutex::new(
+            QueueManager::with_store(Box::new(Arc::clone(&store))).unwrap(),
+        ));
+        let mut worker = Worker::new().with_queue(Arc::clone(&queue));
+        worker.register("test_func", test_task).unwrap();
+        worker.register("fails", always_fails).unwrap();
+
+        let mut failed = job("fails", "Failed", 1);
+        failed.max_retries = 1;
+        worker.run_job(&mut failed);
+        assert!(store.load_history(10).is_empty(), "a retry is not final");
+        let mut retry = queue.lock().unwrap().pop().unwrap();
+        worker.run_job(&mut retry);
+        let mut succeeded = job("test_func", "Succeeded", 1);
+        worker.run_job(&mut succeeded);
+
+        let history = store.load_history(10);
+        assert_eq!(history.len(), 2);
+        assert_eq!(history[0].id, failed.id);
+        assert_eq!(history[0].status, Status::Failed);
+        assert_eq!(history[1].status, Status::Success);
+        assert!(history.iter().all(|j| j.completed_at.is_some()));
+        assert_eq!(queue.lock().unwrap().history(1)[0].id, succeeded.id);
+    }
 }